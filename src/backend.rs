@@ -0,0 +1,160 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BundleID, UnnamedError, geometry::Rect, reducer::Command};
+
+pub mod fake;
+#[cfg(target_os = "macos")]
+pub mod macos;
+
+/// How far apart, in points, the edges of a window can be from where it is
+/// meant to be and still count as there. Apps round frames to whole pixels,
+/// so they rarely end up exactly where they were put.
+pub const FRAME_TOLERANCE: f64 = 1.0;
+
+/// Identifies a window for as long as it is open.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct WindowId(pub u64);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppInfo {
+    pub pid: i32,
+    pub bundle_id: String,
+}
+
+/// What the layout engine knows about a window when it first sees it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub id: WindowId,
+    pub frame: Rect,
+    pub title: String,
+    /// The accessibility role, such as `AXWindow`.
+    pub role: String,
+    /// The accessibility subrole, such as `AXStandardWindow` or `AXDialog`.
+    pub subrole: Option<String>,
+    /// Whether the window blocks the rest of its app until it is dismissed.
+    pub modal: bool,
+}
+
+impl WindowInfo {
+    /// Whether the window is an ordinary document window rather than a
+    /// dialog, sheet, panel or the like, which only make sense at the size
+    /// their app gave them.
+    pub fn is_standard(&self) -> bool {
+        self.role == "AXWindow"
+            && self.subrole.as_deref() == Some("AXStandardWindow")
+            && !self.modal
+    }
+}
+
+/// Something that happened to a window of an app passed to
+/// [`WindowBackend::subscribe`], or to the displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendEvent {
+    WindowMoved(WindowId),
+    WindowResized(WindowId),
+    WindowClosed(WindowId),
+    /// A display was connected, disconnected, moved or changed resolution.
+    DisplaysChanged,
+}
+
+/// Everything the layout engine needs from the windowing system.
+///
+/// How [`BackendEvent`]s are delivered is up to the implementation: see
+/// [`macos::MacosBackend::new`] and [`fake::FakeBackend::poll_event`].
+pub trait WindowBackend {
+    fn running_apps(
+        &mut self,
+        bundle_id: BundleID,
+    ) -> Result<Vec<AppInfo>, UnnamedError>;
+
+    /// Returns `None` if no app is frontmost.
+    fn focused_app(&mut self) -> Result<Option<AppInfo>, UnnamedError>;
+
+    fn windows(&mut self, app: &AppInfo)
+    -> Result<Vec<WindowId>, UnnamedError>;
+
+    /// The window of `app` that would get keyboard input if `app` were
+    /// frontmost, or `None` if there is none.
+    fn focused_window(
+        &mut self,
+        app: &AppInfo,
+    ) -> Result<Option<WindowId>, UnnamedError>;
+
+    /// The app owning `window`, which must have been returned by
+    /// [`WindowBackend::windows`].
+    fn window_app(&mut self, window: WindowId)
+    -> Result<AppInfo, UnnamedError>;
+
+    /// Where `window` currently is.
+    fn frame(&mut self, window: WindowId) -> Result<Rect, UnnamedError>;
+
+    /// `window` must have been returned by [`WindowBackend::windows`].
+    fn window_info(
+        &mut self,
+        window: WindowId,
+    ) -> Result<WindowInfo, UnnamedError>;
+
+    /// [`WindowBackend::windows`] along with what each of them is.
+    fn window_infos(
+        &mut self,
+        app: &AppInfo,
+    ) -> Result<Vec<WindowInfo>, UnnamedError> {
+        self.windows(app)?
+            .into_iter()
+            .map(|window| self.window_info(window))
+            .collect()
+    }
+
+    /// Does nothing if `window` is already within [`FRAME_TOLERANCE`] of
+    /// `frame`.
+    fn set_frame(
+        &mut self,
+        window: WindowId,
+        frame: Rect,
+    ) -> Result<(), UnnamedError>;
+
+    /// Brings `window` and its app to the front.
+    fn raise(&mut self, window: WindowId) -> Result<(), UnnamedError>;
+
+    /// Starts reporting moves, resizes and closing of the current windows of
+    /// `app`.
+    fn subscribe(&mut self, app: &AppInfo) -> Result<(), UnnamedError>;
+
+    /// Stops reporting anything about `window` and lets go of everything
+    /// kept for it, once a [`BackendEvent::WindowClosed`] says it is gone.
+    fn forget(&mut self, window: WindowId);
+
+    fn execute(&mut self, command: &Command) -> Result<(), UnnamedError> {
+        match command {
+            Command::SetFrame { window, frame } => {
+                self.set_frame(*window, *frame)
+            }
+            Command::Raise(window) => self.raise(*window),
+        }
+    }
+}
@@ -0,0 +1,684 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An in-memory [`WindowBackend`] that runs anywhere, for exercising layout
+//! behavior without a window server.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use super::{
+    AppInfo, BackendEvent, FRAME_TOLERANCE, WindowBackend, WindowId, WindowInfo,
+};
+use crate::{
+    BundleID, UnnamedError,
+    geometry::{Rect, Size},
+};
+
+struct FakeWindow {
+    pid: i32,
+    frame: Rect,
+    title: String,
+    role: String,
+    subrole: Option<String>,
+    modal: bool,
+    /// The only size the app lets the window have, if it insists on one.
+    fixed_size: Option<Size>,
+}
+
+/// Apps and windows are created by the test script rather than discovered.
+/// Like the real window server, moving or resizing a window of a subscribed
+/// app (whether through [`WindowBackend::set_frame`] or [`FakeBackend::drag`])
+/// or closing it queues a [`BackendEvent`], which can be read back with
+/// [`FakeBackend::poll_event`]. Apps can also be made to refuse sizes with
+/// [`FakeBackend::fix_size`].
+#[derive(Default)]
+pub struct FakeBackend {
+    apps: Vec<AppInfo>,
+    windows: BTreeMap<WindowId, FakeWindow>,
+    focused: Option<i32>,
+    /// The focused window of each app, by PID.
+    focused_windows: BTreeMap<i32, WindowId>,
+    /// The window most recently brought to the front.
+    raised: Option<WindowId>,
+    subscribed: HashSet<i32>,
+    events: VecDeque<BackendEvent>,
+    next_pid: i32,
+    next_window_id: u64,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn launch(&mut self, bundle_id: BundleID) -> AppInfo {
+        let app = AppInfo {
+            pid: self.next_pid + 1,
+            bundle_id: bundle_id.to_string(),
+        };
+        self.insert_app(app.clone());
+        app
+    }
+
+    /// Adds an app with a known PID, such as one seen in a recorded trace.
+    /// Does nothing if an app with that PID is already running.
+    pub fn insert_app(&mut self, app: AppInfo) {
+        if self.app(app.pid).is_ok() {
+            return;
+        }
+        self.next_pid = self.next_pid.max(app.pid);
+        self.apps.push(app);
+    }
+
+    /// Quits `app`, closing all of its windows.
+    pub fn quit(&mut self, app: &AppInfo) {
+        self.apps.retain(|other| other.pid != app.pid);
+        self.windows.retain(|_, window| window.pid != app.pid);
+        self.focused_windows.remove(&app.pid);
+        self.subscribed.remove(&app.pid);
+        if self.focused == Some(app.pid) {
+            self.focused = None;
+        }
+    }
+
+    /// Opens an untitled standard window.
+    pub fn open_window(&mut self, app: &AppInfo, frame: Rect) -> WindowId {
+        let id = WindowId(self.next_window_id + 1);
+        self.insert_window(
+            app,
+            WindowInfo {
+                id,
+                frame,
+                title: String::new(),
+                role: "AXWindow".into(),
+                subrole: Some("AXStandardWindow".into()),
+                modal: false,
+            },
+        );
+        id
+    }
+
+    /// Opens a window with a known ID, such as one seen in a recorded trace.
+    /// Does nothing if a window with that ID is already open.
+    pub fn insert_window(&mut self, app: &AppInfo, window: WindowInfo) {
+        if self.windows.contains_key(&window.id) {
+            return;
+        }
+        self.next_window_id = self.next_window_id.max(window.id.0);
+        self.windows.insert(
+            window.id,
+            FakeWindow {
+                pid: app.pid,
+                frame: window.frame,
+                title: window.title,
+                role: window.role,
+                subrole: window.subrole,
+                modal: window.modal,
+                fixed_size: None,
+            },
+        );
+    }
+
+    pub fn close_window(&mut self, window: WindowId) {
+        let Some(closed) = self.windows.remove(&window) else {
+            return;
+        };
+        self.focused_windows.retain(|_, focused| *focused != window);
+        if self.subscribed.contains(&closed.pid) {
+            self.events.push_back(BackendEvent::WindowClosed(window));
+        }
+    }
+
+    pub fn focus(&mut self, app: &AppInfo) {
+        self.focused = Some(app.pid);
+    }
+
+    /// Focuses `window` and its app, as if the user clicked on it.
+    pub fn focus_window(
+        &mut self,
+        window: WindowId,
+    ) -> Result<(), UnnamedError> {
+        let pid = self.window(window)?.pid;
+        self.focused = Some(pid);
+        self.focused_windows.insert(pid, window);
+        Ok(())
+    }
+
+    /// Moves `window` as if the user dragged it.
+    pub fn drag(
+        &mut self,
+        window: WindowId,
+        frame: Rect,
+    ) -> Result<(), UnnamedError> {
+        self.set_frame(window, frame)
+    }
+
+    /// Makes the app of `window` keep it at `size` from now on, as if it
+    /// rounded every size it was given to that, like terminals rounding to
+    /// whole rows of text. Moves still go where they are told.
+    pub fn fix_size(
+        &mut self,
+        window: WindowId,
+        size: Size,
+    ) -> Result<(), UnnamedError> {
+        self.windows
+            .get_mut(&window)
+            .ok_or(UnnamedError::UnknownWindow { window })?
+            .fixed_size = Some(size);
+        Ok(())
+    }
+
+    /// The window most recently raised through [`WindowBackend::raise`], if
+    /// it is still open.
+    pub fn raised(&self) -> Option<WindowId> {
+        self.raised
+            .filter(|window| self.windows.contains_key(window))
+    }
+
+    pub fn poll_event(&mut self) -> Option<BackendEvent> {
+        self.events.pop_front()
+    }
+
+    /// Every open window and its current frame, in the order they were opened.
+    pub fn frames(&self) -> impl Iterator<Item = (WindowId, Rect)> + '_ {
+        self.windows.iter().map(|(id, window)| (*id, window.frame))
+    }
+
+    fn app(&self, pid: i32) -> Result<&AppInfo, UnnamedError> {
+        self.apps
+            .iter()
+            .find(|app| app.pid == pid)
+            .ok_or(UnnamedError::UnknownApp { pid })
+    }
+
+    fn window(&self, window: WindowId) -> Result<&FakeWindow, UnnamedError> {
+        self.windows
+            .get(&window)
+            .ok_or(UnnamedError::UnknownWindow { window })
+    }
+}
+
+impl WindowBackend for FakeBackend {
+    fn running_apps(
+        &mut self,
+        bundle_id: BundleID,
+    ) -> Result<Vec<AppInfo>, UnnamedError> {
+        Ok(self
+            .apps
+            .iter()
+            .filter(|app| app.bundle_id == bundle_id.as_ref())
+            .cloned()
+            .collect())
+    }
+
+    fn focused_app(&mut self) -> Result<Option<AppInfo>, UnnamedError> {
+        self.focused.map(|pid| self.app(pid).cloned()).transpose()
+    }
+
+    fn windows(
+        &mut self,
+        app: &AppInfo,
+    ) -> Result<Vec<WindowId>, UnnamedError> {
+        Ok(self
+            .windows
+            .iter()
+            .filter(|(_, window)| window.pid == app.pid)
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    fn focused_window(
+        &mut self,
+        app: &AppInfo,
+    ) -> Result<Option<WindowId>, UnnamedError> {
+        Ok(self.focused_windows.get(&app.pid).copied())
+    }
+
+    fn window_app(
+        &mut self,
+        window: WindowId,
+    ) -> Result<AppInfo, UnnamedError> {
+        let pid = self.window(window)?.pid;
+        self.app(pid).cloned()
+    }
+
+    fn frame(&mut self, window: WindowId) -> Result<Rect, UnnamedError> {
+        Ok(self.window(window)?.frame)
+    }
+
+    fn window_info(
+        &mut self,
+        window: WindowId,
+    ) -> Result<WindowInfo, UnnamedError> {
+        let fake_window = self.window(window)?;
+        Ok(WindowInfo {
+            id: window,
+            frame: fake_window.frame,
+            title: fake_window.title.clone(),
+            role: fake_window.role.clone(),
+            subrole: fake_window.subrole.clone(),
+            modal: fake_window.modal,
+        })
+    }
+
+    fn set_frame(
+        &mut self,
+        window: WindowId,
+        frame: Rect,
+    ) -> Result<(), UnnamedError> {
+        let fake_window = self
+            .windows
+            .get_mut(&window)
+            .ok_or(UnnamedError::UnknownWindow { window })?;
+        let old_frame = fake_window.frame;
+        let requested = frame;
+        let frame = Rect {
+            size: fake_window.fixed_size.unwrap_or(frame.size),
+            ..frame
+        };
+        let refused = !frame.approx_eq(&requested, FRAME_TOLERANCE);
+        if old_frame.approx_eq(&frame, FRAME_TOLERANCE) && !refused {
+            return Ok(());
+        }
+        fake_window.frame = frame;
+
+        // A refused size is taken and then given back, which is still a
+        // resize even if the window ends up where it started.
+        if self.subscribed.contains(&fake_window.pid) {
+            if old_frame.origin != frame.origin {
+                self.events.push_back(BackendEvent::WindowMoved(window));
+            }
+            if old_frame.size != frame.size || refused {
+                self.events.push_back(BackendEvent::WindowResized(window));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn raise(&mut self, window: WindowId) -> Result<(), UnnamedError> {
+        self.focus_window(window)?;
+        self.raised = Some(window);
+        Ok(())
+    }
+
+    fn subscribe(&mut self, app: &AppInfo) -> Result<(), UnnamedError> {
+        self.app(app.pid)?;
+        self.subscribed.insert(app.pid);
+        Ok(())
+    }
+
+    fn forget(&mut self, _window: WindowId) {
+        // Closing a window already removed it.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::CoreApp,
+        keybind::{Chord, Key, Modifiers, default_keybindings},
+        layout::{Gaps, Layout, Layouts, default_slots},
+        reducer::{Command, Event, MAX_REFUSALS, State, reduce},
+    };
+
+    const SCREEN: Rect = Rect::new(0.0, 0.0, 1000.0, 800.0);
+
+    fn kitty() -> BundleID<'static> {
+        BundleID::try_from("net.kovidgoyal.kitty").unwrap()
+    }
+
+    fn layouts() -> Layouts {
+        Layouts::new(SCREEN, Gaps::default(), default_slots())
+    }
+
+    fn state(layout: Layout) -> State {
+        State::new(
+            vec![layouts()],
+            &[CoreApp {
+                bundle_id: kitty().to_string(),
+                layout,
+                managed: true,
+            }],
+            vec![],
+            default_keybindings(),
+        )
+    }
+
+    /// Reduces `event` and carries out the commands, like the daemon does.
+    fn dispatch(
+        state: &mut State,
+        backend: &mut FakeBackend,
+        event: Event,
+    ) -> Vec<Command> {
+        let commands = reduce(state, event);
+        for command in &commands {
+            backend.execute(command).unwrap();
+        }
+        commands
+    }
+
+    fn launched(backend: &mut FakeBackend, app: &AppInfo) -> Event {
+        Event::AppLaunched {
+            app: app.clone(),
+            windows: backend.window_infos(app).unwrap(),
+        }
+    }
+
+    /// Dispatches queued events, and the events they cause, until there are
+    /// none left, returning how many frames were set along the way.
+    fn settle(state: &mut State, backend: &mut FakeBackend) -> usize {
+        let mut set = 0;
+        for _ in 0..100 {
+            let event = match backend.poll_event() {
+                Some(BackendEvent::WindowMoved(window)) => Event::WindowMoved {
+                    window,
+                    frame: backend.frame(window).unwrap(),
+                },
+                Some(BackendEvent::WindowResized(window)) => {
+                    Event::WindowResized {
+                        window,
+                        frame: backend.frame(window).unwrap(),
+                    }
+                }
+                Some(event) => panic!("Unexpected {event:?}"),
+                None => return set,
+            };
+            set += dispatch(state, backend, event)
+                .iter()
+                .filter(|command| matches!(command, Command::SetFrame { .. }))
+                .count();
+        }
+        panic!("Windows never stopped moving");
+    }
+
+    #[test]
+    fn launched_windows_are_laid_out() {
+        let mut backend = FakeBackend::new();
+        let mut state = state(Layout::LEFT);
+        let app = backend.launch(kitty());
+        let window =
+            backend.open_window(&app, Rect::new(40.0, 40.0, 300.0, 200.0));
+
+        let event = launched(&mut backend, &app);
+        let commands = dispatch(&mut state, &mut backend, event);
+
+        let left = layouts().get(&Layout::LEFT).unwrap();
+        assert_eq!(
+            commands,
+            [Command::SetFrame {
+                window,
+                frame: left
+            }]
+        );
+        assert_eq!(backend.frames().collect::<Vec<_>>(), [(window, left)]);
+        assert_eq!(backend.window_app(window).unwrap(), app);
+    }
+
+    #[test]
+    fn set_frame_within_tolerance_does_nothing() {
+        let mut backend = FakeBackend::new();
+        let app = backend.launch(kitty());
+        let frame = Rect::new(10.0, 10.0, 300.0, 200.0);
+        let window = backend.open_window(&app, frame);
+        backend.subscribe(&app).unwrap();
+
+        let nudged = Rect::new(10.5, 9.5, 300.5, 200.0);
+        backend.set_frame(window, nudged).unwrap();
+        assert_eq!(backend.frame(window).unwrap(), frame);
+        assert_eq!(backend.poll_event(), None);
+
+        let moved = Rect::new(12.0, 10.0, 300.0, 200.0);
+        backend.set_frame(window, moved).unwrap();
+        assert_eq!(backend.frame(window).unwrap(), moved);
+    }
+
+    #[test]
+    fn changes_to_subscribed_apps_are_queued() {
+        let mut backend = FakeBackend::new();
+        let mut state = state(Layout::FULL);
+        let app = backend.launch(kitty());
+        let other =
+            backend.launch(BundleID::try_from("com.apple.Safari").unwrap());
+        let window =
+            backend.open_window(&app, Rect::new(40.0, 40.0, 300.0, 200.0));
+        let unwatched =
+            backend.open_window(&other, Rect::new(40.0, 40.0, 300.0, 200.0));
+        backend.subscribe(&app).unwrap();
+
+        let event = launched(&mut backend, &app);
+        dispatch(&mut state, &mut backend, event);
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowMoved(window))
+        );
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowResized(window))
+        );
+        assert_eq!(backend.poll_event(), None);
+
+        let full = layouts().get(&Layout::FULL).unwrap();
+        let mut dragged = full;
+        dragged.origin.x += 50.0;
+        backend.drag(window, dragged).unwrap();
+        backend.drag(unwatched, dragged).unwrap();
+        backend.close_window(unwatched);
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowMoved(window))
+        );
+        assert_eq!(backend.poll_event(), None);
+
+        // The user moved it, so it goes back where it belongs.
+        let commands = dispatch(
+            &mut state,
+            &mut backend,
+            Event::WindowMoved {
+                window,
+                frame: dragged,
+            },
+        );
+        assert_eq!(
+            commands,
+            [Command::SetFrame {
+                window,
+                frame: full
+            }]
+        );
+        assert_eq!(backend.frame(window).unwrap(), full);
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowMoved(window))
+        );
+
+        backend.close_window(window);
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowClosed(window))
+        );
+        assert_eq!(backend.poll_event(), None);
+    }
+
+    #[test]
+    fn focus_window_focuses_its_app() {
+        let mut backend = FakeBackend::new();
+        let app = backend.launch(kitty());
+        let first = backend.open_window(&app, SCREEN);
+        let second = backend.open_window(&app, SCREEN);
+        assert_eq!(backend.focused_app().unwrap(), None);
+
+        backend.focus_window(second).unwrap();
+        assert_eq!(backend.focused_app().unwrap(), Some(app.clone()));
+        assert_eq!(backend.focused_window(&app).unwrap(), Some(second));
+
+        backend.close_window(second);
+        assert_eq!(backend.focused_window(&app).unwrap(), None);
+        assert!(backend.focus_window(second).is_err());
+        backend.focus_window(first).unwrap();
+        assert_eq!(backend.focused_window(&app).unwrap(), Some(first));
+    }
+
+    #[test]
+    fn raise_brings_window_to_front() {
+        let mut backend = FakeBackend::new();
+        let mut state = state(Layout::MONOCLE);
+        let app = backend.launch(kitty());
+        let first = backend.open_window(&app, SCREEN);
+        let second = backend.open_window(&app, SCREEN);
+        backend.focus_window(first).unwrap();
+
+        let event = Event::AppFocused {
+            app: app.clone(),
+            windows: backend.window_infos(&app).unwrap(),
+            focused_window: backend.focused_window(&app).unwrap(),
+        };
+        dispatch(&mut state, &mut backend, event);
+        assert_eq!(backend.raised(), None);
+
+        let cycle = Chord::new(Modifiers::SUPER, Key::Tab);
+        let commands =
+            dispatch(&mut state, &mut backend, Event::KeyChord(cycle));
+        assert!(commands.contains(&Command::Raise(second)));
+        assert_eq!(backend.raised(), Some(second));
+        assert_eq!(backend.focused_window(&app).unwrap(), Some(second));
+
+        backend.close_window(second);
+        assert_eq!(backend.raised(), None);
+    }
+
+    #[test]
+    fn fixed_sizes_are_refused() {
+        let mut backend = FakeBackend::new();
+        let app = backend.launch(kitty());
+        let frame = Rect::new(10.0, 10.0, 300.0, 200.0);
+        let window = backend.open_window(&app, frame);
+        backend.subscribe(&app).unwrap();
+        backend.fix_size(window, frame.size).unwrap();
+
+        backend
+            .set_frame(window, Rect::new(10.0, 10.0, 500.0, 400.0))
+            .unwrap();
+        assert_eq!(backend.frame(window).unwrap(), frame);
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowResized(window))
+        );
+
+        let moved = Rect::new(50.0, 10.0, 300.0, 200.0);
+        backend.set_frame(window, moved).unwrap();
+        assert_eq!(backend.frame(window).unwrap(), moved);
+        assert_eq!(
+            backend.poll_event(),
+            Some(BackendEvent::WindowMoved(window))
+        );
+        assert_eq!(backend.poll_event(), None);
+    }
+
+    #[test]
+    fn skipped_writes_do_not_count_as_refused() {
+        let mut backend = FakeBackend::new();
+        let mut state = state(Layout::LEFT);
+        let app = backend.launch(kitty());
+        let left = layouts().get(&Layout::LEFT).unwrap();
+        let window = backend.open_window(&app, left);
+        backend.subscribe(&app).unwrap();
+
+        // It is already in place, so nothing is written or heard back.
+        let event = launched(&mut backend, &app);
+        assert_eq!(dispatch(&mut state, &mut backend, event).len(), 1);
+        assert_eq!(backend.poll_event(), None);
+
+        // Now the app refuses every size but its own, and the user drags it,
+        // so it gets the full number of tries.
+        backend.fix_size(window, Size::new(300.0, 200.0)).unwrap();
+        backend
+            .drag(window, Rect::new(40.0, 40.0, 300.0, 200.0))
+            .unwrap();
+        assert_eq!(settle(&mut state, &mut backend), MAX_REFUSALS as usize);
+    }
+
+    #[test]
+    fn echoes_of_our_own_moves_are_ignored() {
+        let mut backend = FakeBackend::new();
+        let mut state = state(Layout::LEFT);
+        let app = backend.launch(kitty());
+        let window =
+            backend.open_window(&app, Rect::new(40.0, 40.0, 300.0, 200.0));
+        backend.subscribe(&app).unwrap();
+
+        let event = launched(&mut backend, &app);
+        dispatch(&mut state, &mut backend, event);
+        assert_eq!(settle(&mut state, &mut backend), 0);
+
+        // Close enough to where it was sent counts as an echo too.
+        let mut nudged = layouts().get(&Layout::LEFT).unwrap();
+        nudged.origin.x += FRAME_TOLERANCE / 2.0;
+        let commands = dispatch(
+            &mut state,
+            &mut backend,
+            Event::WindowMoved {
+                window,
+                frame: nudged,
+            },
+        );
+        assert_eq!(commands, []);
+    }
+
+    #[test]
+    fn refused_frames_are_given_up_on_until_the_layout_changes() {
+        let mut backend = FakeBackend::new();
+        let mut state = state(Layout::LEFT);
+        let app = backend.launch(kitty());
+        let window =
+            backend.open_window(&app, Rect::new(40.0, 40.0, 300.0, 200.0));
+        backend.subscribe(&app).unwrap();
+        backend.fix_size(window, Size::new(300.0, 200.0)).unwrap();
+        backend.focus_window(window).unwrap();
+
+        let event = launched(&mut backend, &app);
+        let commands = dispatch(&mut state, &mut backend, event);
+        assert_eq!(commands.len(), 1);
+        // The first write is refused with both a move and a resize, which
+        // only count once. Counting it, the window is sent to its slot
+        // `MAX_REFUSALS` times before it is left where it settled.
+        assert_eq!(settle(&mut state, &mut backend), MAX_REFUSALS as usize - 1);
+        let settled = backend.frame(window).unwrap();
+        assert_eq!(settled.size, Size::new(300.0, 200.0));
+
+        let event = Event::AppFocused {
+            app: app.clone(),
+            windows: backend.window_infos(&app).unwrap(),
+            focused_window: Some(window),
+        };
+        assert_eq!(dispatch(&mut state, &mut backend, event), []);
+        assert_eq!(backend.frame(window).unwrap(), settled);
+
+        // A new target is worth trying again.
+        let right = Chord::new(Modifiers::SUPER_SHIFT, Key::Char('l'));
+        let commands =
+            dispatch(&mut state, &mut backend, Event::KeyChord(right));
+        let right = layouts().get(&Layout::RIGHT).unwrap();
+        assert_eq!(
+            commands,
+            [Command::SetFrame {
+                window,
+                frame: right
+            }]
+        );
+        assert_eq!(settle(&mut state, &mut backend), MAX_REFUSALS as usize - 1);
+        assert_eq!(backend.frame(window).unwrap().origin, right.origin);
+    }
+}
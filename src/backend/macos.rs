@@ -0,0 +1,578 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, hash_map::Entry},
+    ffi, ptr,
+    time::{Duration, Instant},
+};
+
+use accessibility_sys::{
+    AXObserverAddNotification, AXObserverCreate, AXObserverGetRunLoopSource,
+    AXObserverRef, AXObserverRemoveNotification, AXUIElementRef,
+    kAXErrorNotificationAlreadyRegistered, kAXUIElementDestroyedNotification,
+    kAXWindowMovedNotification, kAXWindowResizedNotification, pid_t,
+};
+use cocoa::{appkit::NSWorkspace, base::nil};
+use core_foundation_sys::{
+    base::{CFEqual, CFTypeRef},
+    date::{CFAbsoluteTimeGetCurrent, CFTimeInterval},
+    runloop::{
+        CFRunLoopAddSource, CFRunLoopAddTimer, CFRunLoopGetCurrent,
+        CFRunLoopRemoveSource, CFRunLoopTimerContext, CFRunLoopTimerCreate,
+        CFRunLoopTimerInvalidate, CFRunLoopTimerRef,
+        CFRunLoopTimerSetNextFireDate, kCFRunLoopDefaultMode,
+    },
+    string::CFStringRef,
+};
+use core_graphics::display::{
+    CGDirectDisplayID, CGDisplayChangeSummaryFlags,
+    CGDisplayRegisterReconfigurationCallback,
+    CGDisplayRemoveReconfigurationCallback,
+};
+use snafu::{ResultExt, whatever};
+
+use super::{
+    AppInfo, BackendEvent, FRAME_TOLERANCE, WindowBackend, WindowId, WindowInfo,
+};
+use crate::{
+    AXErrorExt, BundleID, UnnamedError,
+    debounce::Debouncer,
+    geometry::Rect,
+    layout::create_ax_rect,
+    memory::{CopyOnWrite, Unique},
+    running_apps_with_bundle_id,
+    wrappers::{
+        AccessibilityElement, App, Window, create_cfstring_from_static_str,
+    },
+};
+
+/// Receives [`BackendEvent`]s from the run loop of the thread that called
+/// [`WindowBackend::subscribe`].
+pub type EventSink = fn(BackendEvent);
+
+/// How long a window has to stop moving and resizing before it is reported.
+const SETTLE_DELAY: Duration = Duration::from_millis(50);
+
+/// Long enough that the timer of an [`Inbox`] only fires when it is told to.
+const IDLE_INTERVAL: CFTimeInterval = 60.0 * 60.0 * 24.0 * 365.0;
+
+/// Makes the [`BackendEvent`] reporting a notification about a window.
+type WindowEvent = fn(WindowId) -> BackendEvent;
+
+/// Every notification observed on each window of a subscribed app, and what
+/// it is reported as.
+const NOTIFICATIONS: [(&str, WindowEvent); 3] = [
+    (kAXWindowMovedNotification, BackendEvent::WindowMoved),
+    (kAXWindowResizedNotification, BackendEvent::WindowResized),
+    (
+        kAXUIElementDestroyedNotification,
+        BackendEvent::WindowClosed,
+    ),
+];
+
+/// Holds back window moves and resizes until the window settles, so that a
+/// drag, or an app adjusting its own frame in steps, is reported once. Display
+/// changes are held back the same way, since macOS reports one for every
+/// display involved.
+struct Inbox {
+    sink: EventSink,
+    pending: RefCell<Debouncer<BackendEvent>>,
+    /// Delivers whatever is due whenever it fires.
+    timer: OnceCell<Unique<CFRunLoopTimerRef>>,
+}
+
+impl Inbox {
+    fn push(&self, event: BackendEvent) {
+        let mut pending = self.pending.borrow_mut();
+        pending.push(event, Instant::now());
+        self.schedule(&pending);
+    }
+
+    /// Passes every event that is due to the sink.
+    fn deliver_due(&self) {
+        let due = self.pending.borrow_mut().take_due(Instant::now());
+        // The sink moves windows, so it can't run while `pending` is
+        // borrowed.
+        for event in due {
+            (self.sink)(event);
+        }
+        self.schedule(&self.pending.borrow());
+    }
+
+    /// Makes the timer fire when the next event in `pending` is due.
+    fn schedule(&self, pending: &Debouncer<BackendEvent>) {
+        let (Some(deadline), Some(timer)) =
+            (pending.next_deadline(), self.timer.get())
+        else {
+            return;
+        };
+        let delay = deadline.saturating_duration_since(Instant::now());
+        // SAFETY: The timer is valid for as long as `self` is.
+        unsafe {
+            CFRunLoopTimerSetNextFireDate(
+                timer.get(),
+                CFAbsoluteTimeGetCurrent() + delay.as_secs_f64(),
+            );
+        }
+    }
+}
+
+extern "C" fn timer_callback(
+    _timer: CFRunLoopTimerRef,
+    info: *mut ffi::c_void,
+) {
+    // SAFETY: `MacosBackend::new` always passes its `Inbox` as the info, and
+    // invalidates the timer before dropping the `Inbox`.
+    let inbox = unsafe { &*(info as *const Inbox) };
+    inbox.deliver_due();
+}
+
+/// The refcon of every notification observed on a window. A window that was
+/// destroyed can no longer be asked for its ID, so it is kept here.
+struct Subscription {
+    inbox: *const Inbox,
+    window: WindowId,
+}
+
+unsafe extern "C" fn observer_callback(
+    _observer: AXObserverRef,
+    _element: AXUIElementRef,
+    notification: CFStringRef,
+    refcon: *mut ffi::c_void,
+) {
+    // SAFETY: `MacosBackend::subscribe` always passes a `Subscription` as the
+    // refcon. `MacosBackend::forget` only drops it once the window is
+    // destroyed, after which it is never notified about again, and removes
+    // its notifications first in case it wasn't.
+    let subscription = unsafe { &*(refcon as *const Subscription) };
+    // SAFETY: The `Inbox` of a `MacosBackend` outlives its subscriptions.
+    let inbox = unsafe { &*subscription.inbox };
+
+    for (name, event) in NOTIFICATIONS {
+        let name = match create_cfstring_from_static_str(name) {
+            Ok(name) => name,
+            Err(error) => {
+                eprintln!("error: {error}");
+                return;
+            }
+        };
+        // SAFETY: Both are valid CFStrings.
+        if unsafe {
+            CFEqual(notification as CFTypeRef, name.get() as CFTypeRef)
+        } != 0
+        {
+            inbox.push(event(subscription.window));
+            return;
+        }
+    }
+}
+
+unsafe extern "C" fn display_callback(
+    _display: CGDirectDisplayID,
+    flags: u32,
+    user_info: *const ffi::c_void,
+) {
+    // Every change is reported once before it happens and once after, and
+    // the displays are only worth reading after.
+    if CGDisplayChangeSummaryFlags::from_bits_truncate(flags)
+        .contains(CGDisplayChangeSummaryFlags::kCGDisplayBeginConfigurationFlag)
+    {
+        return;
+    }
+
+    // SAFETY: `MacosBackend::new` always passes its `Inbox` as the user info,
+    // and removes the callback before dropping the `Inbox`.
+    let inbox = unsafe { &*(user_info as *const Inbox) };
+    inbox.push(BackendEvent::DisplaysChanged);
+}
+
+pub struct MacosBackend {
+    windows: HashMap<WindowId, Window>,
+    /// Only of apps with subscribed windows.
+    observers: HashMap<pid_t, Unique<AXObserverRef>>,
+    /// Boxed so observers can keep a pointer to them, and declared after
+    /// `observers` so they outlive them. The window of each is in `windows`.
+    subscriptions: HashMap<WindowId, Box<Subscription>>,
+    /// Boxed so subscriptions and callbacks can keep a pointer to it.
+    inbox: Box<Inbox>,
+}
+
+impl MacosBackend {
+    /// `sink` is called on the current run loop for every window move,
+    /// resize or close in subscribed apps, once the window has stopped
+    /// changing for a moment, and once the displays stop changing. A change can
+    /// be reported more than once.
+    pub fn new(sink: EventSink) -> Result<Self, UnnamedError> {
+        let inbox = Box::new(Inbox {
+            sink,
+            pending: RefCell::new(Debouncer::new(SETTLE_DELAY)),
+            timer: OnceCell::new(),
+        });
+        let mut context = CFRunLoopTimerContext {
+            version: 0,
+            info: &*inbox as *const Inbox as *mut ffi::c_void,
+            retain: None,
+            release: None,
+            copyDescription: None,
+        };
+        // SAFETY: `timer_callback` expects an `Inbox` as the info, which the
+        // timer is invalidated before dropping, and `context` is copied.
+        let timer = unsafe {
+            Unique::new_mut(CFRunLoopTimerCreate(
+                ptr::null(),
+                CFAbsoluteTimeGetCurrent() + IDLE_INTERVAL,
+                IDLE_INTERVAL,
+                0,
+                0,
+                timer_callback,
+                &mut context,
+            ))
+        }
+        .ok_or(UnnamedError::CouldNotCreateCFObject)?;
+        // SAFETY: `timer` was just created.
+        unsafe {
+            CFRunLoopAddTimer(
+                CFRunLoopGetCurrent(),
+                timer.get(),
+                kCFRunLoopDefaultMode,
+            );
+        }
+        let _ = inbox.timer.set(timer);
+
+        // SAFETY: `display_callback` expects an `Inbox` as the user info,
+        // which the callback is removed before dropping.
+        let result = unsafe {
+            CGDisplayRegisterReconfigurationCallback(
+                display_callback,
+                &*inbox as *const Inbox as *const ffi::c_void,
+            )
+        };
+        if result != 0 {
+            whatever!("Failed to observe displays (error {result})");
+        }
+
+        Ok(Self {
+            windows: HashMap::new(),
+            observers: HashMap::new(),
+            subscriptions: HashMap::new(),
+            inbox,
+        })
+    }
+
+    fn window(&self, window: WindowId) -> Result<&Window, UnnamedError> {
+        self.windows
+            .get(&window)
+            .ok_or(UnnamedError::UnknownWindow { window })
+    }
+
+    /// Creates an observer for `pid` on the current run loop unless one
+    /// exists already.
+    fn ensure_observer(&mut self, pid: pid_t) -> Result<(), UnnamedError> {
+        if let Entry::Vacant(entry) = self.observers.entry(pid) {
+            let mut observer = ptr::null_mut();
+            // SAFETY: `observer_callback` has the signature of an
+            // `AXObserverCallback`, and `observer` is a valid place to write
+            // the new observer to.
+            unsafe { AXObserverCreate(pid, observer_callback, &mut observer) }
+                .into_result()?;
+            // SAFETY: `AXObserverCreate` succeeded, so `observer` was just
+            // created with a retain count of one, and it is only used through
+            // this `Unique` from now on.
+            let observer = unsafe { Unique::new_mut(observer) }
+                .ok_or(UnnamedError::UnexpectedNull)?;
+
+            // SAFETY: `observer` is valid until it is dropped, and the source
+            // it returns is owned by it rather than by us.
+            let run_loop_source =
+                unsafe { AXObserverGetRunLoopSource(observer.get()) };
+            if run_loop_source.is_null() {
+                return Err(UnnamedError::UnexpectedNull);
+            }
+            // SAFETY: `run_loop_source` was just checked to be non-null, and
+            // the run loop retains it, so it outlives `observer` if need be.
+            // `remove_observer` takes it out again.
+            unsafe {
+                CFRunLoopAddSource(
+                    CFRunLoopGetCurrent(),
+                    run_loop_source,
+                    kCFRunLoopDefaultMode,
+                )
+            };
+
+            entry.insert(observer);
+        }
+
+        Ok(())
+    }
+
+    /// Stops observing `pid` on the current run loop.
+    fn remove_observer(&mut self, pid: pid_t) {
+        let Some(observer) = self.observers.remove(&pid) else {
+            return;
+        };
+        // SAFETY: `observer` is valid until it is dropped at the end of this
+        // function.
+        let run_loop_source =
+            unsafe { AXObserverGetRunLoopSource(observer.get()) };
+        if !run_loop_source.is_null() {
+            // SAFETY: `ensure_observer` added this source to the current run
+            // loop in this mode, and it was just checked to be non-null.
+            unsafe {
+                CFRunLoopRemoveSource(
+                    CFRunLoopGetCurrent(),
+                    run_loop_source,
+                    kCFRunLoopDefaultMode,
+                )
+            };
+        }
+    }
+}
+
+impl Drop for MacosBackend {
+    fn drop(&mut self) {
+        // SAFETY: These are the arguments `MacosBackend::new` registered.
+        unsafe {
+            CGDisplayRemoveReconfigurationCallback(
+                display_callback,
+                &*self.inbox as *const Inbox as *const ffi::c_void,
+            );
+        }
+        if let Some(timer) = self.inbox.timer.get() {
+            // SAFETY: The timer is valid until `self.inbox` is dropped.
+            unsafe { CFRunLoopTimerInvalidate(timer.get()) };
+        }
+    }
+}
+
+impl WindowBackend for MacosBackend {
+    fn running_apps(
+        &mut self,
+        bundle_id: BundleID,
+    ) -> Result<Vec<AppInfo>, UnnamedError> {
+        Ok(running_apps_with_bundle_id(bundle_id)?
+            .iter()
+            .map(|app| AppInfo {
+                pid: app.pid(),
+                bundle_id: app.bundle_id().to_string(),
+            })
+            .collect())
+    }
+
+    fn focused_app(&mut self) -> Result<Option<AppInfo>, UnnamedError> {
+        // SAFETY: `sharedWorkspace` is a class method, so it takes `nil`.
+        let workspace = unsafe { NSWorkspace::sharedWorkspace(nil) };
+        if workspace.is_null() {
+            return Err(UnnamedError::UnexpectedNull);
+        }
+
+        // SAFETY: `workspace` was just checked to be a non-null
+        // `NSWorkspace`, which lives for the whole process.
+        let app = unsafe { NSWorkspace::frontmostApplication(workspace) };
+        if app.is_null() {
+            return Ok(None);
+        }
+
+        // SAFETY: `frontmostApplication` returns an `NSRunningApplication`,
+        // which was just checked to be non-null. We don't own it, so it is
+        // borrowed rather than released when `app` is dropped.
+        let app = unsafe { App::from_nsapp(CopyOnWrite::Borrowed(app), None) }?;
+
+        Ok(Some(AppInfo {
+            pid: app.pid(),
+            bundle_id: app.bundle_id().to_string(),
+        }))
+    }
+
+    fn windows(
+        &mut self,
+        app: &AppInfo,
+    ) -> Result<Vec<WindowId>, UnnamedError> {
+        let pid = app.pid;
+        let app = App::from_pid(pid, app.bundle_id.as_str())?;
+
+        let mut ids = vec![];
+        for window in app.get_windows()? {
+            let id = window.id();
+            self.windows.insert(id, window);
+            ids.push(id);
+        }
+        // Windows that closed without being subscribed to are never reported,
+        // so this is when they are let go of. Subscribed ones are let go of
+        // in `forget`, which needs them to stop observing them.
+        self.windows.retain(|id, window| {
+            window.pid() != pid
+                || ids.contains(id)
+                || self.subscriptions.contains_key(id)
+        });
+
+        Ok(ids)
+    }
+
+    fn focused_window(
+        &mut self,
+        app: &AppInfo,
+    ) -> Result<Option<WindowId>, UnnamedError> {
+        let app = App::from_pid(app.pid, app.bundle_id.as_str())?;
+        Ok(app.focused_window()?.map(|window| {
+            let id = window.id();
+            self.windows.insert(id, window);
+            id
+        }))
+    }
+
+    fn window_app(
+        &mut self,
+        window: WindowId,
+    ) -> Result<AppInfo, UnnamedError> {
+        let window = self.window(window)?;
+        Ok(AppInfo {
+            pid: window.pid(),
+            bundle_id: window.bundle_id().to_string(),
+        })
+    }
+
+    fn frame(&mut self, window: WindowId) -> Result<Rect, UnnamedError> {
+        self.window(window)?.frame()
+    }
+
+    fn window_info(
+        &mut self,
+        window: WindowId,
+    ) -> Result<WindowInfo, UnnamedError> {
+        let frame = self.frame(window)?;
+        let ax_window = self.window(window)?;
+        Ok(WindowInfo {
+            id: window,
+            frame,
+            title: ax_window.title()?,
+            role: ax_window.role()?,
+            subrole: ax_window.subrole()?,
+            modal: ax_window.is_modal()?,
+        })
+    }
+
+    fn set_frame(
+        &mut self,
+        window: WindowId,
+        frame: Rect,
+    ) -> Result<(), UnnamedError> {
+        let ax_window = self
+            .windows
+            .get_mut(&window)
+            .ok_or(UnnamedError::UnknownWindow { window })?;
+        // Reading is cheaper than writing, which also makes the app fire
+        // moved and resized notifications even if nothing changed.
+        if ax_window
+            .frame()
+            .is_ok_and(|current| current.approx_eq(&frame, FRAME_TOLERANCE))
+        {
+            return Ok(());
+        }
+
+        let ax_rect = create_ax_rect(frame)?;
+        ax_window.relayout(&ax_rect)
+    }
+
+    fn raise(&mut self, window: WindowId) -> Result<(), UnnamedError> {
+        self.window(window)?.raise()
+    }
+
+    fn subscribe(&mut self, app: &AppInfo) -> Result<(), UnnamedError> {
+        let windows = self.windows(app)?;
+        self.ensure_observer(app.pid)?;
+        // SAFETY: Observers are only removed by `forget`, so this one stays in
+        // `self.observers`, and valid, until this function returns.
+        let observer = unsafe { self.observers[&app.pid].get() };
+
+        let inbox = &*self.inbox as *const Inbox;
+        for id in windows {
+            // A window keeps its subscription until it is forgotten, so its
+            // refcon stays the same however many times it is subscribed to.
+            let subscription =
+                &**self.subscriptions.entry(id).or_insert_with(|| {
+                    Box::new(Subscription { inbox, window: id })
+                }) as *const Subscription as *mut ffi::c_void;
+            let window = self.window(id)?;
+
+            for (notification, _) in NOTIFICATIONS {
+                let notification_cfstring =
+                    create_cfstring_from_static_str(notification)?;
+
+                // SAFETY: `observer` is valid as above, and `window` and
+                // `notification_cfstring` are valid for the call, which
+                // retains whatever it keeps of them. The
+                // `Subscription` behind `subscription` stays boxed in
+                // `self.subscriptions` until `forget` removes this
+                // notification again, as `observer_callback` relies on.
+                let result = unsafe {
+                    AXObserverAddNotification(
+                        observer,
+                        window.inner(),
+                        notification_cfstring.get(),
+                        subscription,
+                    )
+                };
+                if result != kAXErrorNotificationAlreadyRegistered {
+                    result.into_result().whatever_context(format!(
+                        "Failed to observe {notification} in {}",
+                        app.bundle_id
+                    ))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+    fn forget(&mut self, window: WindowId) {
+        let subscription = self.subscriptions.remove(&window);
+        let Some(ax_window) = self.windows.remove(&window) else {
+            return;
+        };
+        let pid = ax_window.pid();
+
+        if let Some(observer) = self.observers.get(&pid) {
+            for (notification, _) in NOTIFICATIONS {
+                let Ok(notification) =
+                    create_cfstring_from_static_str(notification)
+                else {
+                    continue;
+                };
+                // This fails if the window is already destroyed, as it usually
+                // is, but then it is never notified about again anyway.
+                //
+                // SAFETY: The observer and window are valid until dropped, and
+                // the notification is a valid CFString.
+                let _ = unsafe {
+                    AXObserverRemoveNotification(
+                        observer.get(),
+                        ax_window.inner(),
+                        notification.get(),
+                    )
+                };
+            }
+        }
+        drop(subscription);
+
+        let observed = self.subscriptions.keys().any(|id| {
+            self.windows
+                .get(id)
+                .is_some_and(|window| window.pid() == pid)
+        });
+        if !observed {
+            self.remove_observer(pid);
+        }
+    }
+}
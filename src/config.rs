@@ -0,0 +1,929 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Settings read from a TOML file, for example:
+//!
+//! ```toml
+//! [[apps]]
+//! bundle_id = "net.kovidgoyal.kitty"
+//! layout = "left"
+//!
+//! [[apps]]
+//! bundle_id = "com.apple.mail"
+//! floating = true
+//!
+//! [layouts]
+//! wide-left = [{ axis = "horizontal", ratio = 0.6, side = "first" }]
+//! coding = "hsplit(0.6, editor, vsplit(0.5, terminal, browser))"
+//!
+//! [gaps]
+//! outer = 8
+//! top = 6
+//! inner = 12
+//!
+//! [screen]
+//! top = 38
+//!
+//! [[rules]]
+//! bundle_id = "com.apple.*"
+//! title = "^Settings"
+//! float = true
+//!
+//! [keybindings]
+//! "super-shift-f" = "full"
+//! ```
+//!
+//! Files that don't end in `.toml` are read in the legacy format instead,
+//! which has one core app bundle ID per line, optionally followed by its
+//! layout.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+use toml::Spanned;
+
+use crate::{
+    BundleID, BundleIDParseError, UnnamedError,
+    geometry::{Insets, Rect},
+    keybind::{Action, Chord, KeybindParseError, default_keybindings},
+    layout::{
+        Gaps, Layout, LayoutParseError, Layouts, Slot, default_slots,
+        expression::{self, ExpressionParseError},
+    },
+    rules::Rule,
+};
+
+/// An app whose windows are laid out from the moment it is found running.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreApp {
+    pub bundle_id: String,
+    pub layout: Layout,
+    pub managed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub core_apps: Vec<CoreApp>,
+    /// The default slots with those from the file added on top.
+    pub slots: BTreeMap<Layout, Slot>,
+    pub gaps: GapsConfig,
+    pub screen: ScreenConfig,
+    /// In the order they apply.
+    pub rules: Vec<Rule>,
+    /// The default keybindings with those from the file added on top.
+    pub keybindings: HashMap<Chord, Action>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            core_apps: vec![],
+            slots: default_slots(),
+            gaps: GapsConfig::default(),
+            screen: ScreenConfig::default(),
+            rules: vec![],
+            keybindings: default_keybindings(),
+        }
+    }
+}
+
+/// Every field is optional and falls back to [`Gaps::default`].
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GapsConfig {
+    /// Every outer edge, unless overridden by one of the fields below.
+    pub outer: Option<f64>,
+    pub top: Option<f64>,
+    pub left: Option<f64>,
+    pub bottom: Option<f64>,
+    pub right: Option<f64>,
+    pub inner: Option<f64>,
+}
+
+impl GapsConfig {
+    pub fn resolve(&self) -> Gaps {
+        let mut gaps = Gaps::default();
+
+        if let Some(outer) = self.outer {
+            gaps.outer = Insets::uniform(outer);
+        }
+        gaps.outer.top = self.top.unwrap_or(gaps.outer.top);
+        gaps.outer.left = self.left.unwrap_or(gaps.outer.left);
+        gaps.outer.bottom = self.bottom.unwrap_or(gaps.outer.bottom);
+        gaps.outer.right = self.right.unwrap_or(gaps.outer.right);
+        gaps.inner = self.inner.unwrap_or(gaps.inner);
+
+        gaps
+    }
+}
+
+/// How much of each edge of a screen belongs to the menu bar, the Dock and
+/// the like. Every field is optional and falls back to what macOS reports.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenConfig {
+    pub top: Option<f64>,
+    pub left: Option<f64>,
+    pub bottom: Option<f64>,
+    pub right: Option<f64>,
+}
+
+impl ScreenConfig {
+    /// The part of a screen that windows can go in, given its whole `frame`
+    /// and the part of it that macOS considers `visible`.
+    pub fn usable_area(&self, frame: Rect, visible: Rect) -> Rect {
+        frame.inset(Insets {
+            top: self.top.unwrap_or(visible.min_y() - frame.min_y()),
+            left: self.left.unwrap_or(visible.min_x() - frame.min_x()),
+            bottom: self.bottom.unwrap_or(frame.max_y() - visible.max_y()),
+            right: self.right.unwrap_or(frame.max_x() - visible.max_x()),
+        })
+    }
+}
+
+/// Lines and columns are 1-indexed.
+#[derive(Debug, Snafu)]
+pub enum ConfigError {
+    #[snafu(display("{line}:{column}: {message}"))]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[snafu(display("{line}:{column}: Invalid bundle ID"))]
+    InvalidBundleID {
+        line: usize,
+        column: usize,
+        source: BundleIDParseError,
+    },
+    #[snafu(display("{line}:{column}: Invalid keybinding"))]
+    InvalidKeybinding {
+        line: usize,
+        column: usize,
+        source: KeybindParseError,
+    },
+    #[snafu(display("{line}:{column}: Invalid layout"))]
+    InvalidLayout {
+        line: usize,
+        column: usize,
+        source: LayoutParseError,
+    },
+    #[snafu(display("{line}:{column}: Invalid layout expression '{name}'"))]
+    InvalidExpression {
+        line: usize,
+        column: usize,
+        name: String,
+        source: ExpressionParseError,
+    },
+    #[snafu(display("{line}:{column}: Layout '{name}' is already defined"))]
+    LayoutRedefined {
+        line: usize,
+        column: usize,
+        name: String,
+    },
+    #[snafu(display(
+        "{line}:{column}: Split ratio {ratio} is not strictly between 0 and 1"
+    ))]
+    InvalidRatio {
+        line: usize,
+        column: usize,
+        ratio: f64,
+    },
+}
+
+/// The line and column of the byte at `offset` in `source`.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    apps: Vec<RawApp>,
+    layouts: BTreeMap<String, Spanned<RawLayout>>,
+    gaps: GapsConfig,
+    screen: ScreenConfig,
+    rules: Vec<Spanned<Rule>>,
+    keybindings: BTreeMap<Spanned<String>, Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLayout {
+    /// Defines every slot named in an [`expression`].
+    Expression(String),
+    /// Defines a slot with the name of the entry.
+    Slot(Slot),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawApp {
+    bundle_id: Spanned<String>,
+    layout: Option<Spanned<String>>,
+    #[serde(default)]
+    floating: bool,
+}
+
+/// `start` is the offset of `bundle_id` in `source`.
+fn validate_bundle_id(
+    source: &str,
+    bundle_id: &str,
+    start: usize,
+) -> Result<(), ConfigError> {
+    BundleID::try_from(bundle_id).map(|_| ()).map_err(|error| {
+        let index = match error {
+            BundleIDParseError::Empty => 0,
+            BundleIDParseError::InvalidCharacter { index, .. } => index,
+        };
+        let (line, column) = position(source, start + index);
+        ConfigError::InvalidBundleID {
+            line,
+            column,
+            source: error,
+        }
+    })
+}
+
+/// Makes sure the `[layouts]` entry at `start` in `source` doesn't take the
+/// name of a layout that already exists.
+fn validate_new_layout(
+    source: &str,
+    slots: &BTreeMap<Layout, Slot>,
+    name: &str,
+    start: usize,
+) -> Result<(), ConfigError> {
+    let layout = Layout::new(name);
+    if !slots.contains_key(&layout) && !layout.is_tiled() {
+        return Ok(());
+    }
+    let (line, column) = position(source, start);
+    Err(ConfigError::LayoutRedefined {
+        line,
+        column,
+        name: name.to_string(),
+    })
+}
+
+/// `start` is the offset of `layout` in `source`.
+fn validate_layout(
+    source: &str,
+    slots: &BTreeMap<Layout, Slot>,
+    layout: &Layout,
+    start: usize,
+) -> Result<(), ConfigError> {
+    if slots.contains_key(layout) || layout.is_tiled() {
+        return Ok(());
+    }
+    let (line, column) = position(source, start);
+    Err(ConfigError::InvalidLayout {
+        line,
+        column,
+        source: LayoutParseError::new(layout.clone()),
+    })
+}
+
+impl Config {
+    pub fn parse(source: &str) -> Result<Self, ConfigError> {
+        let raw: RawConfig = toml::from_str(source).map_err(|error| {
+            let (line, column) =
+                position(source, error.span().map_or(0, |span| span.start));
+            ConfigError::Syntax {
+                line,
+                column,
+                message: error.message().to_string(),
+            }
+        })?;
+
+        let mut slots = default_slots();
+        for (name, layout) in raw.layouts {
+            let start = layout.span().start;
+            // Entries never replace a layout, and while the name of an
+            // expression only labels it, reusing one would read as doing so.
+            validate_new_layout(source, &slots, &name, start)?;
+            match layout.into_inner() {
+                RawLayout::Expression(source_expression) => {
+                    let named =
+                        expression::parse(&source_expression, |layout| {
+                            slots.contains_key(layout) || layout.is_tiled()
+                        })
+                        .map_err(|error| {
+                            // Skips the opening quote.
+                            let (line, column) =
+                                position(source, start + 1 + error.offset());
+                            ConfigError::InvalidExpression {
+                                line,
+                                column,
+                                name: name.clone(),
+                                source: error,
+                            }
+                        })?;
+                    slots.extend(named);
+                }
+                RawLayout::Slot(slot) => {
+                    if let Some(split) = slot
+                        .splits
+                        .iter()
+                        .find(|split| !(split.ratio > 0.0 && split.ratio < 1.0))
+                    {
+                        let (line, column) = position(source, start);
+                        return Err(ConfigError::InvalidRatio {
+                            line,
+                            column,
+                            ratio: split.ratio,
+                        });
+                    }
+                    slots.insert(Layout::new(name), slot);
+                }
+            }
+        }
+
+        let mut core_apps = vec![];
+        for app in raw.apps {
+            // Skips the opening quote.
+            let start = app.bundle_id.span().start + 1;
+            validate_bundle_id(source, app.bundle_id.get_ref(), start)?;
+
+            let layout = match app.layout {
+                Some(name) => {
+                    let layout = Layout::new(name.get_ref().as_str());
+                    validate_layout(
+                        source,
+                        &slots,
+                        &layout,
+                        name.span().start,
+                    )?;
+                    layout
+                }
+                None => Layout::FULL,
+            };
+
+            core_apps.push(CoreApp {
+                bundle_id: app.bundle_id.into_inner(),
+                layout,
+                managed: !app.floating,
+            });
+        }
+
+        let mut rules = vec![];
+        for rule in raw.rules {
+            if let Some(layout) = &rule.get_ref().layout {
+                validate_layout(source, &slots, layout, rule.span().start)?;
+            }
+            rules.push(rule.into_inner());
+        }
+
+        let mut keybindings = default_keybindings();
+        for (chord, action) in raw.keybindings {
+            let (line, column) = position(source, chord.span().start);
+            let chord = chord
+                .get_ref()
+                .parse()
+                .context(InvalidKeybindingSnafu { line, column })?;
+            let start = action.span().start;
+            let (line, column) = position(source, start);
+            let action = action
+                .get_ref()
+                .parse()
+                .context(InvalidKeybindingSnafu { line, column })?;
+            if let Action::SetLayout(layout) = &action {
+                validate_layout(source, &slots, layout, start)?;
+            }
+            keybindings.insert(chord, action);
+        }
+
+        Ok(Self {
+            core_apps,
+            slots,
+            gaps: raw.gaps,
+            screen: raw.screen,
+            rules,
+            keybindings,
+        })
+    }
+
+    /// Parses a file containing on each line a bundle ID optionally followed
+    /// by a layout, such as `net.kovidgoyal.kitty left`, a comment starting
+    /// with `#`, or whitespace. Every app listed starts out managed, in
+    /// [`Layout::FULL`] unless given another layout.
+    pub fn parse_legacy(source: &str) -> Result<Self, ConfigError> {
+        let mut core_apps = vec![];
+        let mut offset = 0;
+        for line in source.split_inclusive('\n') {
+            let next_line = offset + line.len();
+            let trimmed = line.trim_start();
+            let line_start = offset + (line.len() - trimmed.len());
+            offset = next_line;
+
+            let line = trimmed.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (bundle_id, layout_name) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, ""), |(bundle_id, rest)| {
+                    (bundle_id, rest.trim_start())
+                });
+
+            validate_bundle_id(source, bundle_id, line_start)?;
+            let layout = if layout_name.is_empty() {
+                Layout::FULL
+            } else {
+                let layout = Layout::new(layout_name);
+                validate_layout(
+                    source,
+                    &default_slots(),
+                    &layout,
+                    line_start + (line.len() - layout_name.len()),
+                )?;
+                layout
+            };
+
+            core_apps.push(CoreApp {
+                bundle_id: bundle_id.to_string(),
+                layout,
+                managed: true,
+            });
+        }
+
+        Ok(Self {
+            core_apps,
+            ..Self::default()
+        })
+    }
+
+    /// The layouts of a screen whose usable area is `frame`, with the gaps
+    /// and slots of this config.
+    pub fn layouts(&self, frame: Rect) -> Layouts {
+        Layouts::new(frame, self.gaps.resolve(), self.slots.clone())
+    }
+
+    /// Picks the format based on the extension of `path`.
+    pub fn load(path: &Path) -> Result<Self, UnnamedError> {
+        let source = fs::read_to_string(path).whatever_context(format!(
+            "Failed to read {} as a string",
+            path.display()
+        ))?;
+
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            Self::parse(&source)
+        } else {
+            Self::parse_legacy(&source)
+        }
+        .whatever_context(format!("Invalid config in {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::{AppInfo, WindowId, WindowInfo},
+        keybind::{Key, Modifiers},
+        reducer::{Command, Event, State, reduce},
+    };
+
+    /// The line and column an error was reported at, from its message.
+    fn error_at(source: &str) -> (usize, usize, ConfigError) {
+        let error = Config::parse(source).unwrap_err();
+        let message = error.to_string();
+        let mut parts = message.splitn(3, ':');
+        let line = parts.next().unwrap().parse().unwrap();
+        let column = parts.next().unwrap().parse().unwrap();
+        (line, column, error)
+    }
+
+    fn redefined(source: &str) -> (usize, usize, String) {
+        match Config::parse(source) {
+            Err(ConfigError::LayoutRedefined { line, column, name }) => {
+                (line, column, name)
+            }
+            Err(ConfigError::InvalidExpression {
+                line,
+                column,
+                source: ExpressionParseError::AlreadyDefined { name, .. },
+                ..
+            }) => (line, column, name),
+            other => panic!("expected a redefinition, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_problem() {
+        let (line, column, error) =
+            error_at("[gaps]\ninner = 12\nouter = \"wide\"\n");
+        assert_eq!((line, column), (3, 9));
+        assert!(matches!(error, ConfigError::Syntax { .. }), "{error}");
+
+        let (line, column, error) = error_at("[gaps]\nmiddle = 3\n");
+        assert_eq!((line, column), (2, 1));
+        assert!(matches!(error, ConfigError::Syntax { .. }), "{error}");
+
+        let (line, column, _) = error_at("[[apps]\n");
+        assert_eq!((line, column), (1, 8));
+    }
+
+    #[test]
+    fn invalid_bundle_ids_point_at_the_character() {
+        let (line, column, error) = error_at(
+            "[[apps]]\n\
+             bundle_id = \"net.kovidgoyal.kitty\"\n\
+             [[apps]]\n\
+             bundle_id = \"com.apple/Safari\"\n",
+        );
+        assert_eq!((line, column), (4, 23));
+        assert!(
+            matches!(
+                error,
+                ConfigError::InvalidBundleID {
+                    source: BundleIDParseError::InvalidCharacter {
+                        index: 9,
+                        c: '/'
+                    },
+                    ..
+                }
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn apps_start_managed_in_full_unless_told_otherwise() {
+        let config = Config::parse(
+            "[[apps]]\n\
+             bundle_id = \"net.kovidgoyal.kitty\"\n\
+             [[apps]]\n\
+             bundle_id = \"com.apple.mail\"\n\
+             floating = true\n\
+             [[apps]]\n\
+             bundle_id = \"com.apple.Safari\"\n\
+             layout = \"right\"\n\
+             floating = false\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.core_apps,
+            [
+                CoreApp {
+                    bundle_id: "net.kovidgoyal.kitty".into(),
+                    layout: Layout::FULL,
+                    managed: true,
+                },
+                CoreApp {
+                    bundle_id: "com.apple.mail".into(),
+                    layout: Layout::FULL,
+                    managed: false,
+                },
+                CoreApp {
+                    bundle_id: "com.apple.Safari".into(),
+                    layout: Layout::RIGHT,
+                    managed: true,
+                },
+            ]
+        );
+
+        let (line, column, error) = error_at(
+            "[[apps]]\n\
+             bundle_id = \"net.kovidgoyal.kitty\"\n\
+             layout = \"sideways\"\n",
+        );
+        assert_eq!((line, column), (3, 10));
+        assert!(
+            matches!(error, ConfigError::InvalidLayout { .. }),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn keybindings_add_to_the_defaults() {
+        let config = Config::parse(
+            "[keybindings]\n\
+             \"super-f\" = \"full\"\n\
+             \"Super-Shift-H\" = \"right\"\n\
+             \"cmd-alt-left\" = \"toggle-floating\"\n",
+        )
+        .unwrap();
+        let action = |modifiers, key| {
+            config.keybindings.get(&Chord::new(modifiers, key)).cloned()
+        };
+        assert_eq!(
+            action(Modifiers::SUPER, Key::Char('f')),
+            Some(Action::SetLayout(Layout::FULL))
+        );
+        assert_eq!(
+            action(Modifiers::SUPER_SHIFT, Key::Char('h')),
+            Some(Action::SetLayout(Layout::RIGHT))
+        );
+        let command_option = Modifiers {
+            command: true,
+            option: true,
+            ..Modifiers::default()
+        };
+        assert_eq!(
+            action(command_option, Key::Left),
+            Some(Action::ToggleManaged)
+        );
+        assert_eq!(
+            action(Modifiers::SUPER_SHIFT, Key::Char('l')),
+            Some(Action::SetLayout(Layout::RIGHT))
+        );
+    }
+
+    #[test]
+    fn keybinding_errors_point_at_the_bad_part() {
+        let (line, column, error) = error_at(
+            "[keybindings]\n\
+             \"super-f\" = \"full\"\n\
+             \"hyper-f\" = \"full\"\n",
+        );
+        assert_eq!((line, column), (3, 1));
+        assert!(
+            matches!(
+                error,
+                ConfigError::InvalidKeybinding {
+                    source: KeybindParseError::UnknownModifier { .. },
+                    ..
+                }
+            ),
+            "{error}"
+        );
+
+        let (line, column, error) =
+            error_at("[keybindings]\n\"super-f\" = \"sideways\"\n");
+        assert_eq!((line, column), (2, 13));
+        assert!(
+            matches!(error, ConfigError::InvalidLayout { .. }),
+            "{error}"
+        );
+    }
+
+    fn gaps(source: &str) -> Gaps {
+        Config::parse(source).unwrap().gaps.resolve()
+    }
+
+    #[test]
+    fn gaps_default_to_the_old_constants() {
+        let old = Gaps {
+            outer: Insets::new(6.0, 8.0, 8.0, 8.0),
+            inner: 12.0,
+        };
+        assert_eq!(gaps(""), old);
+        assert_eq!(gaps("[gaps]\n"), old);
+    }
+
+    #[test]
+    fn outer_gaps_cover_every_edge_but_overridden_ones() {
+        assert_eq!(
+            gaps("[gaps]\nouter = 20\n"),
+            Gaps {
+                outer: Insets::uniform(20.0),
+                inner: 12.0,
+            }
+        );
+        assert_eq!(
+            gaps("[gaps]\ntop = 30\nouter = 20\nright = 0\n"),
+            Gaps {
+                outer: Insets::new(30.0, 20.0, 20.0, 0.0),
+                inner: 12.0,
+            }
+        );
+    }
+
+    #[test]
+    fn edges_override_the_defaults_on_their_own() {
+        assert_eq!(
+            gaps("[gaps]\nleft = 0\nbottom = 40\ninner = 4\n"),
+            Gaps {
+                outer: Insets::new(6.0, 0.0, 40.0, 8.0),
+                inner: 4.0,
+            }
+        );
+    }
+
+    #[test]
+    fn reloading_lays_windows_out_with_the_new_gaps() {
+        let screen = Rect::new(0.0, 0.0, 1000.0, 800.0);
+        let source = "[[apps]]\n\
+                      bundle_id = \"net.kovidgoyal.kitty\"\n\
+                      layout = \"left\"\n";
+        let config = Config::parse(source).unwrap();
+        let mut state = State::new(
+            vec![config.layouts(screen)],
+            &config.core_apps,
+            config.rules.clone(),
+            config.keybindings.clone(),
+        );
+        let window = WindowId(1);
+        let commands = reduce(
+            &mut state,
+            Event::AppLaunched {
+                app: AppInfo {
+                    pid: 1,
+                    bundle_id: "net.kovidgoyal.kitty".into(),
+                },
+                windows: vec![WindowInfo {
+                    id: window,
+                    frame: screen,
+                    title: String::new(),
+                    role: "AXWindow".into(),
+                    subrole: Some("AXStandardWindow".into()),
+                    modal: false,
+                }],
+            },
+        );
+        assert_eq!(
+            commands,
+            [Command::SetFrame {
+                window,
+                frame: Rect::new(8.0, 6.0, 486.0, 786.0),
+            }]
+        );
+
+        let reloaded =
+            Config::parse(&format!("{source}[gaps]\nouter = 20\ninner = 0\n"))
+                .unwrap();
+        let commands = reduce(
+            &mut state,
+            Event::ScreensChanged(vec![reloaded.layouts(screen)]),
+        );
+        assert_eq!(
+            commands,
+            [Command::SetFrame {
+                window,
+                frame: Rect::new(20.0, 20.0, 480.0, 760.0),
+            }]
+        );
+    }
+
+    fn managed(bundle_id: &str, layout: Layout) -> CoreApp {
+        CoreApp {
+            bundle_id: bundle_id.into(),
+            layout,
+            managed: true,
+        }
+    }
+
+    #[test]
+    fn legacy_lists_skip_comments_and_blank_lines() {
+        let config = Config::parse_legacy(
+            "# core apps\n\
+             \n\
+             net.kovidgoyal.kitty left\n\
+             \x20  \t\n\
+             \x20 com.apple.Safari   right  \n\
+             \x20 # indented comment\n\
+             com.apple.mail",
+        )
+        .unwrap();
+        assert_eq!(
+            config.core_apps,
+            [
+                managed("net.kovidgoyal.kitty", Layout::LEFT),
+                managed("com.apple.Safari", Layout::RIGHT),
+                managed("com.apple.mail", Layout::FULL),
+            ]
+        );
+        assert_eq!(config.slots, default_slots());
+    }
+
+    #[test]
+    fn legacy_errors_point_at_the_problem() {
+        let error = Config::parse_legacy(
+            "net.kovidgoyal.kitty\n  com.apple.Safari up\n",
+        )
+        .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                ConfigError::InvalidLayout {
+                    line: 2,
+                    column: 20,
+                    ..
+                }
+            ),
+            "{error}"
+        );
+
+        let error =
+            Config::parse_legacy("  com.apple/Safari left\n").unwrap_err();
+        assert!(
+            matches!(
+                error,
+                ConfigError::InvalidBundleID {
+                    line: 1,
+                    column: 12,
+                    ..
+                }
+            ),
+            "{error}"
+        );
+
+        // Layouts from a TOML file don't exist here.
+        assert!(Config::parse_legacy("com.apple.Safari wide-left").is_err());
+    }
+
+    #[test]
+    fn bundle_ids_cannot_be_empty() {
+        let (line, column, error) = error_at("[[apps]]\nbundle_id = \"\"\n");
+        assert_eq!((line, column), (2, 14));
+        assert!(
+            matches!(
+                error,
+                ConfigError::InvalidBundleID {
+                    source: BundleIDParseError::Empty,
+                    ..
+                }
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn expressions_define_their_slots() {
+        let config = Config::parse(
+            "[layouts]\n\
+             coding = \"hsplit(0.6, editor, vsplit(0.5, terminal, browser))\"\n",
+        )
+        .unwrap();
+        for name in ["editor", "terminal", "browser"] {
+            assert!(config.slots.contains_key(&Layout::new(name)), "{name}");
+        }
+        assert!(!config.slots.contains_key(&Layout::new("coding")));
+        assert_eq!(config.slots[&Layout::FULL], default_slots()[&Layout::FULL]);
+    }
+
+    #[test]
+    fn expressions_cannot_redefine_layouts() {
+        assert_eq!(
+            redefined(
+                "[layouts]\n\
+                 coding = \"hsplit(0.6, full, vsplit(0.5, a, b))\"\n"
+            ),
+            (2, 23, "full".into())
+        );
+        assert_eq!(
+            redefined("[layouts]\nmine = \"vsplit(0.5, bsp, _)\"\n"),
+            (2, 21, "bsp".into())
+        );
+    }
+
+    #[test]
+    fn entries_cannot_share_slots() {
+        assert_eq!(
+            redefined(
+                "[layouts]\n\
+                 a = \"hsplit(0.5, shared, _)\"\n\
+                 b = \"hsplit(0.5, _, shared)\"\n"
+            ),
+            (3, 21, "shared".into())
+        );
+        assert_eq!(
+            redefined(
+                "[layouts]\n\
+                 a = \"hsplit(0.5, wide, _)\"\n\
+                 wide = [{ axis = \"horizontal\", ratio = 0.6, side = \"first\" }]\n"
+            ),
+            (3, 8, "wide".into())
+        );
+    }
+
+    #[test]
+    fn entry_names_cannot_be_layouts() {
+        assert_eq!(
+            redefined("[layouts]\nleft = \"hsplit(0.5, a, b)\"\n"),
+            (2, 8, "left".into())
+        );
+        assert_eq!(
+            redefined(
+                "[layouts]\n\
+                 full = [{ axis = \"horizontal\", ratio = 0.6, side = \"first\" }]\n"
+            ),
+            (2, 8, "full".into())
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! AppKit and the accessibility API disagree on where the origin is. Cocoa
+//! puts it at the bottom-left corner of the primary display, with y growing
+//! upward, while the accessibility API puts it at the top-left corner of the
+//! primary display, with y growing downward. Displays above or left of the
+//! primary one have negative coordinates in one space or the other.
+//!
+//! [`Rect`]s everywhere else are in accessibility space, so frames from
+//! AppKit, such as those of `NSScreen`, go through [`DisplaySpace`] first.
+
+use crate::geometry::Rect;
+
+/// Converts between the two spaces for one arrangement of displays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplaySpace {
+    /// Of the primary display, the one holding the menu bar.
+    primary_height: f64,
+}
+
+impl DisplaySpace {
+    /// `displays` are the Cocoa frames of every display, with the origin of
+    /// each at its bottom-left corner. Returns `None` if none of them is the
+    /// primary display, whose Cocoa frame starts at the origin.
+    pub fn new(displays: &[Rect]) -> Option<Self> {
+        displays
+            .iter()
+            .find(|display| display.origin.x == 0.0 && display.origin.y == 0.0)
+            .map(|primary| Self {
+                primary_height: primary.size.height,
+            })
+    }
+
+    /// `rect` is in Cocoa space, with its origin at its bottom-left corner.
+    pub fn cocoa_to_ax(&self, rect: Rect) -> Rect {
+        Rect::new(
+            rect.origin.x,
+            self.primary_height - rect.max_y(),
+            rect.size.width,
+            rect.size.height,
+        )
+    }
+
+    /// The inverse of [`DisplaySpace::cocoa_to_ax`].
+    pub fn ax_to_cocoa(&self, rect: Rect) -> Rect {
+        // Flipping about the same line twice gets back where it started.
+        self.cocoa_to_ax(rect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In Cocoa space: a 1440x900 primary display, a 1920x1080 display above
+    /// it, and a 1280x1024 display to its left hanging 200 points below it.
+    const PRIMARY: Rect = Rect::new(0.0, 0.0, 1440.0, 900.0);
+    const ABOVE: Rect = Rect::new(0.0, 900.0, 1920.0, 1080.0);
+    const LEFT: Rect = Rect::new(-1280.0, -200.0, 1280.0, 1024.0);
+
+    fn space() -> DisplaySpace {
+        DisplaySpace::new(&[ABOVE, LEFT, PRIMARY]).unwrap()
+    }
+
+    #[test]
+    fn primary_display_keeps_its_origin() {
+        let ax = space().cocoa_to_ax(PRIMARY);
+        assert_eq!(ax, Rect::new(0.0, 0.0, 1440.0, 900.0));
+        assert_eq!(space().ax_to_cocoa(ax), PRIMARY);
+    }
+
+    #[test]
+    fn display_above_has_negative_y() {
+        let ax = space().cocoa_to_ax(ABOVE);
+        assert_eq!(ax, Rect::new(0.0, -1080.0, 1920.0, 1080.0));
+        assert_eq!(space().ax_to_cocoa(ax), ABOVE);
+    }
+
+    #[test]
+    fn display_at_negative_origin_round_trips() {
+        let ax = space().cocoa_to_ax(LEFT);
+        assert_eq!(ax, Rect::new(-1280.0, 76.0, 1280.0, 1024.0));
+        assert_eq!(space().ax_to_cocoa(ax), LEFT);
+    }
+
+    #[test]
+    fn no_primary_display_means_no_space() {
+        assert_eq!(DisplaySpace::new(&[ABOVE, LEFT]), None);
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Holds back things that happen in bursts, such as the moves of a window
+//! being dragged, until they stop happening for a while. Time is passed in
+//! rather than read, so nothing in here depends on a clock.
+
+use std::time::{Duration, Instant};
+
+pub struct Debouncer<T> {
+    delay: Duration,
+    /// Ordered by deadline, since pushing always moves a value to the back.
+    pending: Vec<(T, Instant)>,
+}
+
+impl<T: PartialEq> Debouncer<T> {
+    /// Values are due `delay` after they were last pushed.
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: vec![],
+        }
+    }
+
+    /// Makes `value` due `delay` after `now`, replacing the deadline it had
+    /// if it was already pending.
+    pub fn push(&mut self, value: T, now: Instant) {
+        self.pending.retain(|(pending, _)| *pending != value);
+        self.pending.push((value, now + self.delay));
+    }
+
+    /// Removes every value that is due at `now`, in the order they became
+    /// due.
+    pub fn take_due(&mut self, now: Instant) -> Vec<T> {
+        let due = self
+            .pending
+            .iter()
+            .take_while(|(_, deadline)| *deadline <= now)
+            .count();
+        self.pending.drain(..due).map(|(value, _)| value).collect()
+    }
+
+    /// When the next value is due, or `None` if none are pending.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.first().map(|(_, deadline)| *deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELAY: Duration = Duration::from_millis(50);
+
+    fn ms(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn values_are_due_after_the_delay() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(DELAY);
+        assert_eq!(debouncer.next_deadline(), None);
+
+        debouncer.push(1, start);
+        assert_eq!(debouncer.next_deadline(), Some(ms(start, 50)));
+        assert!(debouncer.take_due(ms(start, 49)).is_empty());
+        assert_eq!(debouncer.take_due(ms(start, 50)), [1]);
+        assert!(debouncer.take_due(ms(start, 100)).is_empty());
+        assert_eq!(debouncer.next_deadline(), None);
+    }
+
+    #[test]
+    fn pushing_again_pushes_back_the_deadline() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(DELAY);
+        debouncer.push(1, start);
+        debouncer.push(2, ms(start, 10));
+        debouncer.push(1, ms(start, 30));
+
+        assert_eq!(debouncer.next_deadline(), Some(ms(start, 60)));
+        assert_eq!(debouncer.take_due(ms(start, 60)), [2]);
+        assert!(debouncer.take_due(ms(start, 79)).is_empty());
+        assert_eq!(debouncer.take_due(ms(start, 80)), [1]);
+    }
+
+    #[test]
+    fn due_values_come_out_in_order() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(DELAY);
+        debouncer.push(3, start);
+        debouncer.push(1, ms(start, 5));
+        debouncer.push(2, ms(start, 100));
+
+        assert_eq!(debouncer.take_due(ms(start, 100)), [3, 1]);
+        assert_eq!(debouncer.next_deadline(), Some(ms(start, 150)));
+    }
+}
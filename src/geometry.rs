@@ -0,0 +1,300 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Size {
+    pub const fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+}
+
+/// A rectangle whose origin is its top-left corner, matching the coordinate
+/// space used by the accessibility API.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub origin: Point,
+    pub size: Size,
+}
+
+/// Distances to pull each edge of a [`Rect`] inward by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Insets {
+    pub top: f64,
+    pub left: f64,
+    pub bottom: f64,
+    pub right: f64,
+}
+
+impl Insets {
+    pub const fn new(top: f64, left: f64, bottom: f64, right: f64) -> Self {
+        Self {
+            top,
+            left,
+            bottom,
+            right,
+        }
+    }
+
+    pub const fn uniform(inset: f64) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+}
+
+impl Rect {
+    pub const fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            origin: Point::new(x, y),
+            size: Size::new(width, height),
+        }
+    }
+
+    /// The rectangle with top-left corner `min` and bottom-right corner `max`.
+    fn spanning(min: Point, max: Point) -> Self {
+        Self::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+
+    pub const fn min_x(&self) -> f64 {
+        self.origin.x
+    }
+
+    pub const fn min_y(&self) -> f64 {
+        self.origin.y
+    }
+
+    pub const fn max_x(&self) -> f64 {
+        self.origin.x + self.size.width
+    }
+
+    pub const fn max_y(&self) -> f64 {
+        self.origin.y + self.size.height
+    }
+
+    pub const fn area(&self) -> f64 {
+        self.size.width * self.size.height
+    }
+
+    /// Splits `self` into a left and a right part, where the left part takes
+    /// up `fraction` of the width.
+    pub fn split_horizontal(&self, fraction: f64) -> (Self, Self) {
+        let left_width = self.size.width * fraction;
+
+        let mut left = *self;
+        left.size.width = left_width;
+
+        let mut right = *self;
+        right.origin.x += left_width;
+        right.size.width -= left_width;
+
+        (left, right)
+    }
+
+    /// Splits `self` into a top and a bottom part, where the top part takes up
+    /// `fraction` of the height.
+    pub fn split_vertical(&self, fraction: f64) -> (Self, Self) {
+        let top_height = self.size.height * fraction;
+
+        let mut top = *self;
+        top.size.height = top_height;
+
+        let mut bottom = *self;
+        bottom.origin.y += top_height;
+        bottom.size.height -= top_height;
+
+        (top, bottom)
+    }
+
+    pub fn inset(&self, insets: Insets) -> Self {
+        Self::new(
+            self.origin.x + insets.left,
+            self.origin.y + insets.top,
+            self.size.width - (insets.left + insets.right),
+            self.size.height - (insets.top + insets.bottom),
+        )
+    }
+
+    /// The smallest rectangle containing both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::spanning(
+            Point::new(
+                self.min_x().min(other.min_x()),
+                self.min_y().min(other.min_y()),
+            ),
+            Point::new(
+                self.max_x().max(other.max_x()),
+                self.max_y().max(other.max_y()),
+            ),
+        )
+    }
+
+    /// The overlap of `self` and `other`, or `None` if they do not overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let min = Point::new(
+            self.min_x().max(other.min_x()),
+            self.min_y().max(other.min_y()),
+        );
+        let max = Point::new(
+            self.max_x().min(other.max_x()),
+            self.max_y().min(other.max_y()),
+        );
+        (min.x < max.x && min.y < max.y).then(|| Self::spanning(min, max))
+    }
+
+    /// Whether every edge of `self` is within `tolerance` of the same edge of
+    /// `other`.
+    pub fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        (self.min_x() - other.min_x()).abs() <= tolerance
+            && (self.min_y() - other.min_y()).abs() <= tolerance
+            && (self.max_x() - other.max_x()).abs() <= tolerance
+            && (self.max_y() - other.max_y()).abs() <= tolerance
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use core_graphics::display::{CGPoint, CGRect, CGSize};
+
+    use super::{Point, Rect, Size};
+
+    impl From<CGPoint> for Point {
+        fn from(value: CGPoint) -> Self {
+            Self::new(value.x, value.y)
+        }
+    }
+
+    impl From<Point> for CGPoint {
+        fn from(value: Point) -> Self {
+            CGPoint::new(value.x, value.y)
+        }
+    }
+
+    impl From<CGSize> for Size {
+        fn from(value: CGSize) -> Self {
+            Self::new(value.width, value.height)
+        }
+    }
+
+    impl From<Size> for CGSize {
+        fn from(value: Size) -> Self {
+            CGSize::new(value.width, value.height)
+        }
+    }
+
+    impl From<CGRect> for Rect {
+        fn from(value: CGRect) -> Self {
+            Self {
+                origin: value.origin.into(),
+                size: value.size.into(),
+            }
+        }
+    }
+
+    impl From<Rect> for CGRect {
+        fn from(value: Rect) -> Self {
+            CGRect::new(&value.origin.into(), &value.size.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Rect = Rect::new(10.0, 20.0, 400.0, 300.0);
+
+    #[test]
+    fn split_horizontal_divides_width() {
+        let (left, right) = FRAME.split_horizontal(0.25);
+        assert_eq!(left, Rect::new(10.0, 20.0, 100.0, 300.0));
+        assert_eq!(right, Rect::new(110.0, 20.0, 300.0, 300.0));
+        assert_eq!(left.max_x(), right.min_x());
+    }
+
+    #[test]
+    fn split_vertical_divides_height() {
+        let (top, bottom) = FRAME.split_vertical(0.5);
+        assert_eq!(top, Rect::new(10.0, 20.0, 400.0, 150.0));
+        assert_eq!(bottom, Rect::new(10.0, 170.0, 400.0, 150.0));
+        assert_eq!(top.max_y(), bottom.min_y());
+    }
+
+    #[test]
+    fn inset_pulls_each_edge_in() {
+        assert_eq!(
+            FRAME.inset(Insets::new(1.0, 2.0, 3.0, 4.0)),
+            Rect::new(12.0, 21.0, 394.0, 296.0)
+        );
+        assert_eq!(
+            FRAME.inset(Insets::uniform(-10.0)),
+            Rect::new(0.0, 10.0, 420.0, 320.0)
+        );
+    }
+
+    #[test]
+    fn intersection_is_the_overlap() {
+        let other = Rect::new(300.0, 0.0, 400.0, 100.0);
+        assert_eq!(
+            FRAME.intersection(&other),
+            Some(Rect::new(300.0, 20.0, 110.0, 80.0))
+        );
+        assert_eq!(other.intersection(&FRAME), FRAME.intersection(&other));
+        assert_eq!(FRAME.intersection(&FRAME), Some(FRAME));
+    }
+
+    #[test]
+    fn touching_rects_do_not_intersect() {
+        let (left, right) = FRAME.split_horizontal(0.5);
+        assert_eq!(left.intersection(&right), None);
+        let far = Rect::new(1000.0, 1000.0, 10.0, 10.0);
+        assert_eq!(FRAME.intersection(&far), None);
+    }
+
+    #[test]
+    fn union_spans_both() {
+        let other = Rect::new(-100.0, 50.0, 50.0, 400.0);
+        assert_eq!(FRAME.union(&other), Rect::new(-100.0, 20.0, 510.0, 430.0));
+        assert_eq!(other.union(&FRAME), FRAME.union(&other));
+        assert_eq!(FRAME.union(&FRAME), FRAME);
+    }
+
+    #[test]
+    fn approx_eq_compares_every_edge() {
+        let nudged = Rect::new(10.5, 19.5, 400.0, 301.0);
+        assert!(FRAME.approx_eq(&nudged, 1.0));
+        assert!(!FRAME.approx_eq(&nudged, 0.25));
+
+        // Same origin, but the right edge is 2 away.
+        let wider = Rect::new(10.0, 20.0, 402.0, 300.0);
+        assert!(!FRAME.approx_eq(&wider, 1.0));
+        assert!(FRAME.approx_eq(&wider, 2.0));
+    }
+}
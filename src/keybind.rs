@@ -0,0 +1,285 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+use crate::layout::Layout;
+
+/// A non-modifier key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Key {
+    /// A lowercase ASCII letter or a digit.
+    Char(char),
+    Space,
+    Return,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub struct Modifiers {
+    pub command: bool,
+    pub control: bool,
+    pub option: bool,
+    pub shift: bool,
+}
+
+impl Modifiers {
+    /// `Command-Control-Option`.
+    pub const SUPER: Self = Self {
+        command: true,
+        control: true,
+        option: true,
+        shift: false,
+    };
+
+    /// `Command-Control-Option-Shift`.
+    pub const SUPER_SHIFT: Self = Self {
+        shift: true,
+        ..Self::SUPER
+    };
+}
+
+/// A key pressed while some modifiers are held down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Chord {
+    pub const fn new(modifiers: Modifiers, key: Key) -> Self {
+        Self { modifiers, key }
+    }
+}
+
+/// What a keybind does to the focused window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    SetLayout(Layout),
+    ToggleManaged,
+    /// Moves the focused window to the next screen, wrapping around after the
+    /// last one.
+    MoveToNextDisplay,
+    MoveToPreviousDisplay,
+    /// Rereads the config file and lays everything out again. This one does
+    /// not involve the focused window.
+    ReloadConfig,
+    /// Moves the first stacked window of [`Layout::MASTER_STACK`] into the
+    /// master column. This and the actions below do not involve the focused
+    /// window either.
+    AddMaster,
+    /// Moves the last master back onto the stack.
+    RemoveMaster,
+    /// Widens the master column.
+    GrowMaster,
+    ShrinkMaster,
+    /// Scrolls [`Layout::SCROLLING`] one column to the left.
+    ScrollLeft,
+    ScrollRight,
+    /// Raises the next window in [`Layout::MONOCLE`].
+    CycleMonocle,
+}
+
+#[derive(Debug, Snafu)]
+pub enum KeybindParseError {
+    #[snafu(display("Unknown modifier '{modifier}' in '{chord}'"))]
+    UnknownModifier { chord: String, modifier: String },
+    #[snafu(display("Unknown key '{key}' in '{chord}'"))]
+    UnknownKey { chord: String, key: String },
+    #[snafu(display("Unknown action '{action}'"))]
+    UnknownAction { action: String },
+}
+
+impl Key {
+    /// Parses a lowercase key name such as `h`, `5` or `space`.
+    fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next())
+            && (c.is_ascii_lowercase() || c.is_ascii_digit())
+        {
+            return Some(Self::Char(c));
+        }
+
+        Some(match name {
+            "space" => Self::Space,
+            "return" => Self::Return,
+            "tab" => Self::Tab,
+            "left" => Self::Left,
+            "right" => Self::Right,
+            "up" => Self::Up,
+            "down" => Self::Down,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Chord {
+    type Err = KeybindParseError;
+
+    /// Parses modifiers and then a key, separated by dashes, such as
+    /// `super-shift-h` or `cmd-ctrl-left`. Case is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_ascii_lowercase();
+        let (modifier_names, key) = match lowercase.rsplit_once('-') {
+            Some((modifier_names, key)) => (Some(modifier_names), key),
+            None => (None, lowercase.as_str()),
+        };
+
+        let mut modifiers = Modifiers::default();
+        for modifier in modifier_names.into_iter().flat_map(|m| m.split('-')) {
+            match modifier {
+                "super" => {
+                    modifiers.command = true;
+                    modifiers.control = true;
+                    modifiers.option = true;
+                }
+                "cmd" | "command" => modifiers.command = true,
+                "ctrl" | "control" => modifiers.control = true,
+                "opt" | "option" | "alt" => modifiers.option = true,
+                "shift" => modifiers.shift = true,
+                _ => {
+                    return Err(KeybindParseError::UnknownModifier {
+                        chord: s.to_string(),
+                        modifier: modifier.to_string(),
+                    });
+                }
+            }
+        }
+
+        let key = Key::from_name(key).ok_or_else(|| {
+            KeybindParseError::UnknownKey {
+                chord: s.to_string(),
+                key: key.to_string(),
+            }
+        })?;
+
+        Ok(Self::new(modifiers, key))
+    }
+}
+
+impl FromStr for Action {
+    type Err = KeybindParseError;
+
+    /// Parses the name of an action, where anything that is not a built-in
+    /// action is taken as the name of a layout.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "" => {
+                return Err(KeybindParseError::UnknownAction {
+                    action: s.to_string(),
+                });
+            }
+            "toggle-floating" => Self::ToggleManaged,
+            "move-to-next-display" => Self::MoveToNextDisplay,
+            "move-to-previous-display" => Self::MoveToPreviousDisplay,
+            "reload-config" => Self::ReloadConfig,
+            "add-master" => Self::AddMaster,
+            "remove-master" => Self::RemoveMaster,
+            "grow-master" => Self::GrowMaster,
+            "shrink-master" => Self::ShrinkMaster,
+            "scroll-left" => Self::ScrollLeft,
+            "scroll-right" => Self::ScrollRight,
+            "cycle-monocle" => Self::CycleMonocle,
+            _ => Self::SetLayout(Layout::new(s)),
+        })
+    }
+}
+
+pub fn default_keybindings() -> HashMap<Chord, Action> {
+    HashMap::from([
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('h')),
+            Action::SetLayout(Layout::LEFT),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('l')),
+            Action::SetLayout(Layout::RIGHT),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('k')),
+            Action::SetLayout(Layout::TOP),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('j')),
+            Action::SetLayout(Layout::BOTTOM),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('c')),
+            Action::SetLayout(Layout::FULL),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('t')),
+            Action::SetLayout(Layout::BSP),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('m')),
+            Action::SetLayout(Layout::MASTER_STACK),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('s')),
+            Action::SetLayout(Layout::SCROLLING),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('o')),
+            Action::SetLayout(Layout::MONOCLE),
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Space),
+            Action::ToggleManaged,
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Char('r')),
+            Action::ReloadConfig,
+        ),
+        (
+            Chord::new(Modifiers::SUPER, Key::Char('i')),
+            Action::AddMaster,
+        ),
+        (
+            Chord::new(Modifiers::SUPER, Key::Char('d')),
+            Action::RemoveMaster,
+        ),
+        (
+            Chord::new(Modifiers::SUPER, Key::Char('l')),
+            Action::GrowMaster,
+        ),
+        (
+            Chord::new(Modifiers::SUPER, Key::Char('h')),
+            Action::ShrinkMaster,
+        ),
+        (Chord::new(Modifiers::SUPER, Key::Left), Action::ScrollLeft),
+        (
+            Chord::new(Modifiers::SUPER, Key::Right),
+            Action::ScrollRight,
+        ),
+        (Chord::new(Modifiers::SUPER, Key::Tab), Action::CycleMonocle),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Right),
+            Action::MoveToNextDisplay,
+        ),
+        (
+            Chord::new(Modifiers::SUPER_SHIFT, Key::Left),
+            Action::MoveToPreviousDisplay,
+        ),
+    ])
+}
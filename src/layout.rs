@@ -12,43 +12,476 @@
 // You should have received a copy of the GNU General Public License along with
 // this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod bsp;
+pub mod expression;
+pub mod master_stack;
+pub mod monocle;
+pub mod scrolling;
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+#[cfg(target_os = "macos")]
 use accessibility_sys::{
     AXValueCreate, AXValueRef, kAXValueTypeCGPoint, kAXValueTypeCGSize,
 };
+#[cfg(target_os = "macos")]
 use cocoa::{
-    appkit::{CGFloat, CGPoint, NSScreen},
+    appkit::NSScreen,
     base::nil,
+    foundation::{NSArray, NSRect},
 };
-use core_graphics::display::{CGRect, CGSize};
+#[cfg(target_os = "macos")]
+use core_graphics::display::{CGPoint, CGRect, CGSize};
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+#[cfg(target_os = "macos")]
+use snafu::whatever;
 
+#[cfg(target_os = "macos")]
 use crate::{UnnamedError, memory::Unique};
+use crate::{
+    backend::WindowId,
+    geometry::{Insets, Rect},
+    keybind::Action,
+};
+#[cfg(target_os = "macos")]
+use crate::{
+    config::Config,
+    coordinates::DisplaySpace,
+    geometry::{Point, Size},
+};
 
+#[cfg(target_os = "macos")]
 pub struct AXRect {
     pub origin: Unique<AXValueRef>,
     pub size: Unique<AXValueRef>,
 }
 
-const LEFT_INSET: CGFloat = 8.0;
-const RIGHT_INSET: CGFloat = 8.0;
-const TOP_INSET: CGFloat = 6.0;
-const BOTTOM_INSET: CGFloat = 8.0;
-const INNER_SPACING: CGFloat = 12.0;
+/// Space left around and between laid out windows.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Gaps {
+    /// Between windows and the edges of the screen.
+    pub outer: Insets,
+    /// Between adjacent windows.
+    pub inner: f64,
+}
+
+impl Default for Gaps {
+    fn default() -> Self {
+        Self {
+            outer: Insets::new(6.0, 8.0, 8.0, 8.0),
+            inner: 12.0,
+        }
+    }
+}
+
+/// The name of a [`Slot`] that windows can be laid out in, such as `left`,
+/// or of a tiling layout such as [`Layout::BSP`].
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Layout(Cow<'static, str>);
+
+impl Layout {
+    pub const FULL: Self = Self(Cow::Borrowed("full"));
+    pub const LEFT: Self = Self(Cow::Borrowed("left"));
+    pub const RIGHT: Self = Self(Cow::Borrowed("right"));
+    pub const TOP: Self = Self(Cow::Borrowed("top"));
+    pub const BOTTOM: Self = Self(Cow::Borrowed("bottom"));
+    /// Tiles the windows of every app in it with a [`bsp::BspTree`] instead
+    /// of putting them all in one slot.
+    pub const BSP: Self = Self(Cow::Borrowed("bsp"));
+    /// Tiles the windows of every app in it with a
+    /// [`master_stack::MasterStack`].
+    pub const MASTER_STACK: Self = Self(Cow::Borrowed("master-stack"));
+    /// Puts the windows of every app in it in columns on a
+    /// [`scrolling::Strip`].
+    pub const SCROLLING: Self = Self(Cow::Borrowed("scrolling"));
+    /// Stacks the windows of every app in it on top of each other in a
+    /// [`monocle::Monocle`].
+    pub const MONOCLE: Self = Self(Cow::Borrowed("monocle"));
+    /// The layouts picking one of the [`default_algorithms`].
+    pub const TILED: [Self; 4] = [
+        Self::BSP,
+        Self::MASTER_STACK,
+        Self::SCROLLING,
+        Self::MONOCLE,
+    ];
+
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(Cow::Owned(name.into()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether windows in this layout are laid out by one of the
+    /// [`default_algorithms`] rather than put in a slot.
+    pub fn is_tiled(&self) -> bool {
+        Self::TILED.contains(self)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("Unknown layout '{layout}'"))]
+pub struct LayoutParseError {
+    layout: Layout,
+}
+
+impl LayoutParseError {
+    pub fn new(layout: Layout) -> Self {
+        Self { layout }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Axis {
+    /// Side by side.
+    Horizontal,
+    /// One above the other.
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    /// The left or top side.
+    First,
+    /// The right or bottom side.
+    Second,
+}
+
+/// Cuts an area in two along `axis` and keeps one `side`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Split {
+    pub axis: Axis,
+    /// How much of the area the first side takes up, between 0 and 1.
+    pub ratio: f64,
+    pub side: Side,
+}
+
+/// A region of the screen, described by the splits that narrow the whole
+/// screen down to it. No splits at all means the whole screen.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Slot {
+    pub splits: Vec<Split>,
+}
+
+/// Edges within this distance of the screen edge count as on it, so that
+/// fractions like thirds that do not add up exactly still reach it.
+const EDGE_EPSILON: f64 = 1e-9;
+
+impl Slot {
+    pub fn new(splits: impl IntoIterator<Item = (Axis, f64, Side)>) -> Self {
+        Self {
+            splits: splits
+                .into_iter()
+                .map(|(axis, ratio, side)| Split { axis, ratio, side })
+                .collect(),
+        }
+    }
+
+    pub fn half(axis: Axis, side: Side) -> Self {
+        Self::new([(axis, 0.5, side)])
+    }
+
+    /// The quarter of the screen in the `horizontal` side of the `vertical`
+    /// side.
+    pub fn quarter(vertical: Side, horizontal: Side) -> Self {
+        Self::new([
+            (Axis::Vertical, 0.5, vertical),
+            (Axis::Horizontal, 0.5, horizontal),
+        ])
+    }
+
+    /// The `index`th of `count` equal columns, counting from zero.
+    pub fn column(index: usize, count: usize) -> Self {
+        Self::columns(index, 1, count)
+    }
+
+    /// `span` adjacent columns of `count` equal ones, starting from the
+    /// `start`th.
+    ///
+    /// # Panics
+    ///
+    /// If `span` is zero or the columns don't all fit in `count`.
+    pub fn columns(start: usize, span: usize, count: usize) -> Self {
+        assert!(
+            span > 0 && start + span <= count,
+            "Columns {start}..{} are not within 0..{count}",
+            start + span
+        );
+        let mut splits = vec![];
+        if start > 0 {
+            splits.push((
+                Axis::Horizontal,
+                start as f64 / count as f64,
+                Side::Second,
+            ));
+        }
+        let remaining = count - start;
+        if span < remaining {
+            splits.push((
+                Axis::Horizontal,
+                span as f64 / remaining as f64,
+                Side::First,
+            ));
+        }
+        Self::new(splits)
+    }
+
+    /// A rect in the middle of the screen taking up `fraction` of its width
+    /// and height.
+    pub fn centered(fraction: f64) -> Self {
+        let margin = (1.0 - fraction) / 2.0;
+        Self::new([
+            (Axis::Horizontal, margin, Side::Second),
+            (Axis::Horizontal, fraction / (1.0 - margin), Side::First),
+            (Axis::Vertical, margin, Side::Second),
+            (Axis::Vertical, fraction / (1.0 - margin), Side::First),
+        ])
+    }
+
+    /// The part of a unit square that the splits narrow it down to.
+    fn unit_rect(&self) -> Rect {
+        self.splits
+            .iter()
+            .fold(Rect::new(0.0, 0.0, 1.0, 1.0), |area, split| {
+                let (first, second) = match split.axis {
+                    Axis::Horizontal => area.split_horizontal(split.ratio),
+                    Axis::Vertical => area.split_vertical(split.ratio),
+                };
+                match split.side {
+                    Side::First => first,
+                    Side::Second => second,
+                }
+            })
+    }
+
+    /// Where this slot lies within `frame`, the usable area of a screen.
+    /// There are outer gaps around `frame` and half an inner gap on each edge
+    /// cut by a split. Ratios are fractions of the screen between the outer
+    /// gaps, so two slots sharing a cut are always exactly an inner gap apart.
+    pub fn frame(&self, frame: Rect, gaps: &Gaps) -> Rect {
+        let usable = frame.inset(gaps.outer);
+        let unit = self.unit_rect();
+        let half_gap =
+            |is_cut: bool| if is_cut { gaps.inner / 2.0 } else { 0.0 };
+
+        Rect::new(
+            usable.min_x() + unit.min_x() * usable.size.width,
+            usable.min_y() + unit.min_y() * usable.size.height,
+            unit.size.width * usable.size.width,
+            unit.size.height * usable.size.height,
+        )
+        .inset(Insets::new(
+            half_gap(unit.min_y() > EDGE_EPSILON),
+            half_gap(unit.min_x() > EDGE_EPSILON),
+            half_gap(unit.max_y() < 1.0 - EDGE_EPSILON),
+            half_gap(unit.max_x() < 1.0 - EDGE_EPSILON),
+        ))
+    }
+}
+
+/// The slots that exist without any configuration: the whole screen, its
+/// halves, quarters and thirds, and a slightly smaller centered rect.
+pub fn default_slots() -> BTreeMap<Layout, Slot> {
+    use Side::{First, Second};
 
-#[derive(Default, Clone, Copy)]
-#[repr(usize)]
-pub enum Layout {
-    #[default]
-    Full,
-    Left,
-    Right,
-    COUNT,
+    BTreeMap::from([
+        (Layout::FULL, Slot::default()),
+        (Layout::LEFT, Slot::half(Axis::Horizontal, First)),
+        (Layout::RIGHT, Slot::half(Axis::Horizontal, Second)),
+        (Layout::TOP, Slot::half(Axis::Vertical, First)),
+        (Layout::BOTTOM, Slot::half(Axis::Vertical, Second)),
+        (Layout::new("top-left"), Slot::quarter(First, First)),
+        (Layout::new("top-right"), Slot::quarter(First, Second)),
+        (Layout::new("bottom-left"), Slot::quarter(Second, First)),
+        (Layout::new("bottom-right"), Slot::quarter(Second, Second)),
+        (Layout::new("left-third"), Slot::column(0, 3)),
+        (Layout::new("center-third"), Slot::column(1, 3)),
+        (Layout::new("right-third"), Slot::column(2, 3)),
+        (Layout::new("first-two-thirds"), Slot::columns(0, 2, 3)),
+        (Layout::new("last-two-thirds"), Slot::columns(1, 2, 3)),
+        (Layout::new("almost-maximize"), Slot::centered(0.9)),
+    ])
 }
 
+/// Everything needed to lay out windows on one screen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Layouts {
-    pub rects: [AXRect; Layout::COUNT as usize],
+    /// The usable area of the screen.
+    pub frame: Rect,
+    pub gaps: Gaps,
+    pub slots: BTreeMap<Layout, Slot>,
 }
 
-fn create_ax_rect(frame: CGRect) -> Result<AXRect, UnnamedError> {
+impl Layouts {
+    pub fn new(frame: Rect, gaps: Gaps, slots: BTreeMap<Layout, Slot>) -> Self {
+        Self { frame, gaps, slots }
+    }
+
+    /// The frame of the slot for `layout` on this screen.
+    pub fn get(&self, layout: &Layout) -> Option<Rect> {
+        self.slots
+            .get(layout)
+            .map(|slot| slot.frame(self.frame, &self.gaps))
+    }
+
+    /// The frame of every slot on this screen.
+    pub fn rects(&self) -> impl Iterator<Item = (&Layout, Rect)> {
+        self.slots
+            .iter()
+            .map(|(layout, slot)| (layout, slot.frame(self.frame, &self.gaps)))
+    }
+}
+
+/// A window to be laid out by a [`LayoutAlgorithm`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowDescriptor {
+    pub id: WindowId,
+    pub bundle_id: String,
+    /// The layout it is assigned.
+    pub layout: Layout,
+    /// Whether it is the focused window.
+    pub focused: bool,
+}
+
+/// Where a window is among windows stacked in the same frame, shown as `2/3`
+/// for the second of three.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackPosition {
+    /// Counting from zero.
+    pub index: usize,
+    pub count: usize,
+}
+
+impl fmt::Display for StackPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index + 1, self.count)
+    }
+}
+
+/// Decides where windows go on a screen.
+///
+/// Every time anything changes, an algorithm is given all of the windows
+/// assigned to it, so algorithms that remember things about windows, such as
+/// [`bsp::BspTree`], should forget windows that are no longer passed in.
+/// Every screen starts out with the [`default_algorithms`], and others can be
+/// installed on any one screen with [`crate::reducer::State::set_algorithm`].
+pub trait LayoutAlgorithm {
+    /// The frame of each of `windows` within `frame`, the usable area of a
+    /// screen. `windows` are in a stable order, and windows left out of the
+    /// result are not moved.
+    fn layout(
+        &mut self,
+        frame: Rect,
+        gaps: &Gaps,
+        windows: &[WindowDescriptor],
+    ) -> Vec<(WindowId, Rect)>;
+
+    /// Reacts to a keybind that does not involve the focused window, returning
+    /// a window to bring to the front if there is one. Windows are laid out
+    /// again afterward either way.
+    fn perform(&mut self, _action: &Action) -> Option<WindowId> {
+        None
+    }
+
+    /// Where `window` is if this algorithm stacks windows on top of each
+    /// other.
+    fn stack_position(&self, _window: WindowId) -> Option<StackPosition> {
+        None
+    }
+
+    /// Tells the algorithm the smallest rect containing every screen, which
+    /// windows must be moved entirely outside of to hide them. Called before
+    /// every [`LayoutAlgorithm::layout`].
+    fn set_bounds(&mut self, _bounds: Rect) {}
+}
+
+/// Puts every window in the slot named by its layout, so windows in the same
+/// slot overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slots<'a> {
+    pub slots: &'a BTreeMap<Layout, Slot>,
+}
+
+impl LayoutAlgorithm for Slots<'_> {
+    fn layout(
+        &mut self,
+        frame: Rect,
+        gaps: &Gaps,
+        windows: &[WindowDescriptor],
+    ) -> Vec<(WindowId, Rect)> {
+        windows
+            .iter()
+            .filter_map(|window| {
+                let slot = self.slots.get(&window.layout)?;
+                Some((window.id, slot.frame(frame, gaps)))
+            })
+            .collect()
+    }
+}
+
+/// The algorithms that exist without any configuration, keyed by the layout
+/// that picks them. Windows in any other layout go in [`Slots`].
+pub fn default_algorithms() -> BTreeMap<Layout, Box<dyn LayoutAlgorithm>> {
+    BTreeMap::from([
+        (
+            Layout::BSP,
+            Box::new(bsp::BspTree::new()) as Box<dyn LayoutAlgorithm>,
+        ),
+        (
+            Layout::MASTER_STACK,
+            Box::new(master_stack::MasterStack::default()),
+        ),
+        (Layout::SCROLLING, Box::new(scrolling::Strip::default())),
+        (Layout::MONOCLE, Box::new(monocle::Monocle::new())),
+    ])
+}
+
+/// The windows in `remembered` that are missing from `windows`, which an
+/// algorithm should forget about.
+fn forgotten(
+    remembered: &[WindowId],
+    windows: &[WindowDescriptor],
+) -> Vec<WindowId> {
+    let given = windows
+        .iter()
+        .map(|window| window.id)
+        .collect::<HashSet<_>>();
+    remembered
+        .iter()
+        .filter(|window| !given.contains(window))
+        .copied()
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn create_ax_rect(frame: Rect) -> Result<AXRect, UnnamedError> {
+    let frame = CGRect::from(frame);
+
     // SAFETY: ``&full_frame.origin` is a valid pointer and not mutably
     // referenced throughout the course of this function.
     let ax_origin = unsafe {
@@ -75,82 +508,145 @@ fn create_ax_rect(frame: CGRect) -> Result<AXRect, UnnamedError> {
     })
 }
 
-fn split_horizontal(frame: CGRect) -> (CGRect, CGRect) {
-    let half_width = frame.size.width / 2.0;
+/// The layouts of every connected screen, starting with the one holding the
+/// menu bar.
+#[cfg(target_os = "macos")]
+pub fn get_screens(config: &Config) -> Result<Vec<Layouts>, UnnamedError> {
+    fn rect(frame: NSRect) -> Rect {
+        Rect {
+            origin: Point::new(frame.origin.x, frame.origin.y),
+            size: Size::new(frame.size.width, frame.size.height),
+        }
+    }
 
-    let mut left = frame;
-    left.size.width = half_width;
+    // SAFETY: `NSScreen` is a class, and `screens` returns an `NSArray` that
+    // is never nil.
+    let screens = unsafe { NSScreen::screens(nil) };
+    // SAFETY: `screens` is an `NSArray`.
+    let count = unsafe { screens.count() };
 
-    let mut right = frame;
-    right.origin.x += half_width;
-    right.size.width = half_width;
+    // The visible frame leaves out the menu bar and the Dock.
+    let frames = (0..count)
+        .map(|index| {
+            // SAFETY: `index` is in bounds, and every element is an
+            // `NSScreen`.
+            let screen = unsafe { screens.objectAtIndex(index) };
+            // SAFETY: `screen` is an `NSScreen`.
+            let (frame, visible) =
+                unsafe { (screen.frame(), screen.visibleFrame()) };
+            (rect(frame), rect(visible))
+        })
+        .collect::<Vec<_>>();
+    let Some(space) = DisplaySpace::new(
+        &frames.iter().map(|(frame, _)| *frame).collect::<Vec<_>>(),
+    ) else {
+        whatever!("No screen is at the origin of the Cocoa coordinate space");
+    };
 
-    (left, right)
+    Ok(frames
+        .into_iter()
+        .map(|(frame, visible)| {
+            config.layouts(config.screen.usable_area(
+                space.cocoa_to_ax(frame),
+                space.cocoa_to_ax(visible),
+            ))
+        })
+        .collect())
 }
 
-fn inset(
-    mut rect: CGRect,
-    left: CGFloat,
-    right: CGFloat,
-    top: CGFloat,
-    bottom: CGFloat,
-) -> CGRect {
-    rect.origin.x += left;
-    rect.size.width -= left + right;
+/// What the tests of each algorithm lay their windows out in.
+#[cfg(test)]
+mod fixtures {
+    use super::{Gaps, Layout, WindowDescriptor};
+    use crate::{
+        backend::WindowId,
+        geometry::{Insets, Rect},
+    };
+
+    pub const FRAME: Rect = Rect::new(0.0, 0.0, 1000.0, 800.0);
+    pub const NO_GAPS: Gaps = gaps(0.0, 0.0);
+
+    /// `outer` around every edge and `inner` between windows.
+    pub const fn gaps(outer: f64, inner: f64) -> Gaps {
+        Gaps {
+            outer: Insets::uniform(outer),
+            inner,
+        }
+    }
 
-    rect.origin.y += top;
-    rect.size.height -= top + bottom;
+    /// The window numbered `id`, from no app in particular.
+    pub fn descriptor(
+        id: u64,
+        layout: Layout,
+        focused: bool,
+    ) -> WindowDescriptor {
+        WindowDescriptor {
+            id: WindowId(id),
+            bundle_id: String::new(),
+            layout,
+            focused,
+        }
+    }
 
-    rect
+    /// `frames` keyed by window number, for comparing against literals.
+    pub fn numbered(frames: Vec<(WindowId, Rect)>) -> Vec<(u64, Rect)> {
+        frames
+            .into_iter()
+            .map(|(window, frame)| (window.0, frame))
+            .collect()
+    }
 }
 
-pub fn get_layouts() -> Result<Layouts, UnnamedError> {
-    // SAFETY: todo
-    let main_screen = unsafe { NSScreen::mainScreen(nil) };
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
 
-    const NOTCH_HEIGHT: CGFloat = 40.0;
+    use super::*;
 
-    let frame = {
-        // SAFETY: todo
-        let frame_nsrect = unsafe { main_screen.frame() };
+    #[test]
+    fn tiled_layouts_are_the_ones_with_default_algorithms() {
+        assert_eq!(
+            default_algorithms().into_keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from(Layout::TILED)
+        );
+        assert!(Layout::MONOCLE.is_tiled());
+        assert!(Layout::new("bsp").is_tiled());
+        assert!(!Layout::FULL.is_tiled());
+    }
 
-        CGRect {
-            origin: CGPoint::new(
-                frame_nsrect.origin.x,
-                frame_nsrect.origin.y + NOTCH_HEIGHT,
-            ),
-            size: CGSize::new(
-                frame_nsrect.size.width,
-                frame_nsrect.size.height - NOTCH_HEIGHT,
-            ),
-        }
-    };
+    #[test]
+    fn columns_divide_the_width_evenly() {
+        assert_eq!(
+            Slot::columns(0, 3, 3).unit_rect(),
+            Rect::new(0.0, 0.0, 1.0, 1.0)
+        );
+        assert!(
+            Slot::column(1, 3)
+                .unit_rect()
+                .approx_eq(&Rect::new(1.0 / 3.0, 0.0, 1.0 / 3.0, 1.0), 1e-9)
+        );
+        assert!(
+            Slot::columns(1, 2, 3)
+                .unit_rect()
+                .approx_eq(&Rect::new(1.0 / 3.0, 0.0, 2.0 / 3.0, 1.0), 1e-9)
+        );
+    }
 
-    let (left_frame, right_frame) = split_horizontal(frame);
-
-    Ok(Layouts {
-        rects: [
-            create_ax_rect(inset(
-                frame,
-                LEFT_INSET,
-                RIGHT_INSET,
-                TOP_INSET,
-                BOTTOM_INSET,
-            ))?,
-            create_ax_rect(inset(
-                left_frame,
-                LEFT_INSET,
-                INNER_SPACING / 2.0,
-                TOP_INSET,
-                BOTTOM_INSET,
-            ))?,
-            create_ax_rect(inset(
-                right_frame,
-                INNER_SPACING / 2.0,
-                RIGHT_INSET,
-                TOP_INSET,
-                BOTTOM_INSET,
-            ))?,
-        ],
-    })
+    #[test]
+    #[should_panic = "Columns 2..4 are not within 0..3"]
+    fn columns_past_the_end_are_rejected() {
+        Slot::columns(2, 2, 3);
+    }
+
+    #[test]
+    #[should_panic = "Columns 4..5 are not within 0..3"]
+    fn columns_starting_past_the_end_are_rejected() {
+        Slot::column(4, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_column_ranges_are_rejected() {
+        Slot::columns(1, 0, 3);
+    }
 }
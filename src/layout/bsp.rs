@@ -0,0 +1,385 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Binary space partitioning: every window is a leaf of a tree of splits, so
+//! windows never overlap and always fill the screen together.
+
+use super::{Axis, Gaps, LayoutAlgorithm, WindowDescriptor, forgotten};
+use crate::{
+    backend::WindowId,
+    geometry::{Insets, Rect},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Leaf(WindowId),
+    Split {
+        axis: Axis,
+        /// How much of the area the first child takes up.
+        ratio: f64,
+        children: Box<[Node; 2]>,
+    },
+}
+
+impl Node {
+    fn contains(&self, window: WindowId) -> bool {
+        match self {
+            Self::Leaf(leaf) => *leaf == window,
+            Self::Split { children, .. } => {
+                children.iter().any(|child| child.contains(window))
+            }
+        }
+    }
+
+    fn leaf_mut(&mut self, window: WindowId) -> Option<&mut Self> {
+        match self {
+            Self::Leaf(leaf) => (*leaf == window).then_some(self),
+            Self::Split { children, .. } => {
+                children.iter_mut().find_map(|child| child.leaf_mut(window))
+            }
+        }
+    }
+
+    fn last_leaf_mut(&mut self) -> &mut Self {
+        match self {
+            Self::Leaf(_) => self,
+            Self::Split { children, .. } => children[1].last_leaf_mut(),
+        }
+    }
+
+    /// `self` with the leaf for `window` removed and its sibling taking the
+    /// place of their parent, or `None` if nothing is left.
+    fn without(self, window: WindowId) -> Option<Self> {
+        match self {
+            Self::Leaf(leaf) => (leaf != window).then_some(self),
+            Self::Split {
+                axis,
+                ratio,
+                children,
+            } => {
+                let [first, second] = *children;
+                match (first.without(window), second.without(window)) {
+                    (Some(first), Some(second)) => Some(Self::Split {
+                        axis,
+                        ratio,
+                        children: Box::new([first, second]),
+                    }),
+                    (Some(remaining), None) | (None, Some(remaining)) => {
+                        Some(remaining)
+                    }
+                    (None, None) => None,
+                }
+            }
+        }
+    }
+
+    fn frames(&self, area: Rect, gaps: &Gaps, out: &mut Vec<(WindowId, Rect)>) {
+        match self {
+            Self::Leaf(window) => out.push((*window, area)),
+            Self::Split {
+                axis,
+                ratio,
+                children,
+            } => {
+                let half_gap = gaps.inner / 2.0;
+                let (first, second) = match axis {
+                    Axis::Horizontal => {
+                        let (left, right) = area.split_horizontal(*ratio);
+                        (
+                            left.inset(Insets {
+                                right: half_gap,
+                                ..Insets::default()
+                            }),
+                            right.inset(Insets {
+                                left: half_gap,
+                                ..Insets::default()
+                            }),
+                        )
+                    }
+                    Axis::Vertical => {
+                        let (top, bottom) = area.split_vertical(*ratio);
+                        (
+                            top.inset(Insets {
+                                bottom: half_gap,
+                                ..Insets::default()
+                            }),
+                            bottom.inset(Insets {
+                                top: half_gap,
+                                ..Insets::default()
+                            }),
+                        )
+                    }
+                };
+                children[0].frames(first, gaps, out);
+                children[1].frames(second, gaps, out);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BspTree {
+    root: Option<Node>,
+}
+
+impl BspTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn contains(&self, window: WindowId) -> bool {
+        self.root.as_ref().is_some_and(|root| root.contains(window))
+    }
+
+    /// Every window in the tree, from left to right.
+    pub fn windows(&self) -> Vec<WindowId> {
+        self.frames(Rect::default(), &Gaps::default())
+            .into_iter()
+            .map(|(window, _)| window)
+            .collect()
+    }
+
+    /// Splits the space of `target` in half along its longer side, keeping
+    /// `target` in the left or top half and putting `window` in the other.
+    /// Splits the last window instead when `target` is not in the tree.
+    /// `frame` and `gaps` are what the tree will be laid out in, and are only
+    /// used to pick the side.
+    pub fn insert(
+        &mut self,
+        window: WindowId,
+        target: Option<WindowId>,
+        frame: Rect,
+        gaps: &Gaps,
+    ) {
+        if self.contains(window) {
+            return;
+        }
+
+        let frames = self.frames(frame, gaps);
+        let split_frame = frames
+            .iter()
+            .find(|(leaf, _)| Some(*leaf) == target)
+            .or(frames.last())
+            .map(|(_, frame)| *frame);
+
+        let Some(root) = &mut self.root else {
+            self.root = Some(Node::Leaf(window));
+            return;
+        };
+        let leaf = match target.and_then(|target| root.leaf_mut(target)) {
+            Some(leaf) => leaf,
+            None => root.last_leaf_mut(),
+        };
+        let Node::Leaf(existing) = *leaf else {
+            unreachable!("Only leaves are returned");
+        };
+
+        let axis = match split_frame {
+            Some(frame) if frame.size.height > frame.size.width => {
+                Axis::Vertical
+            }
+            _ => Axis::Horizontal,
+        };
+        *leaf = Node::Split {
+            axis,
+            ratio: 0.5,
+            children: Box::new([Node::Leaf(existing), Node::Leaf(window)]),
+        };
+    }
+
+    /// Removes `window`, giving its space to its sibling. Returns whether
+    /// `window` was in the tree.
+    pub fn remove(&mut self, window: WindowId) -> bool {
+        if !self.contains(window) {
+            return false;
+        }
+        self.root = self.root.take().and_then(|root| root.without(window));
+        true
+    }
+
+    /// The frame of every window in the tree, from left to right, within
+    /// `frame`, the usable area of a screen.
+    pub fn frames(&self, frame: Rect, gaps: &Gaps) -> Vec<(WindowId, Rect)> {
+        let mut frames = vec![];
+        if let Some(root) = &self.root {
+            root.frames(frame.inset(gaps.outer), gaps, &mut frames);
+        }
+        frames
+    }
+}
+
+impl LayoutAlgorithm for BspTree {
+    /// New windows split the focused window if it is in the tree.
+    fn layout(
+        &mut self,
+        frame: Rect,
+        gaps: &Gaps,
+        windows: &[WindowDescriptor],
+    ) -> Vec<(WindowId, Rect)> {
+        for window in forgotten(&self.windows(), windows) {
+            self.remove(window);
+        }
+        let target = self.windows().into_iter().find(|tiled| {
+            windows
+                .iter()
+                .any(|window| window.id == *tiled && window.focused)
+        });
+        for window in windows {
+            self.insert(window.id, target, frame, gaps);
+        }
+        self.frames(frame, gaps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{
+        Layout,
+        fixtures::{FRAME, NO_GAPS, descriptor, gaps, numbered},
+    };
+
+    fn tree(windows: &[u64], target: Option<u64>) -> BspTree {
+        let mut tree = BspTree::new();
+        for window in windows {
+            tree.insert(
+                WindowId(*window),
+                target.map(WindowId),
+                FRAME,
+                &NO_GAPS,
+            );
+        }
+        tree
+    }
+
+    fn frames(tree: &BspTree, gaps: &Gaps) -> Vec<(u64, Rect)> {
+        numbered(tree.frames(FRAME, gaps))
+    }
+
+    #[test]
+    fn insert_splits_the_longer_side_of_the_target() {
+        let mut tree = tree(&[1, 2], None);
+        assert_eq!(
+            frames(&tree, &NO_GAPS),
+            [
+                (1, Rect::new(0.0, 0.0, 500.0, 800.0)),
+                (2, Rect::new(500.0, 0.0, 500.0, 800.0)),
+            ]
+        );
+
+        // Both halves are taller than they are wide, so this splits the
+        // first one top to bottom and leaves the second alone.
+        tree.insert(WindowId(3), Some(WindowId(1)), FRAME, &NO_GAPS);
+        assert_eq!(
+            frames(&tree, &NO_GAPS),
+            [
+                (1, Rect::new(0.0, 0.0, 500.0, 400.0)),
+                (3, Rect::new(0.0, 400.0, 500.0, 400.0)),
+                (2, Rect::new(500.0, 0.0, 500.0, 800.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_without_a_target_splits_the_last_window() {
+        let tree = tree(&[1, 2, 3], Some(99));
+        assert_eq!(
+            frames(&tree, &NO_GAPS),
+            [
+                (1, Rect::new(0.0, 0.0, 500.0, 800.0)),
+                (2, Rect::new(500.0, 0.0, 500.0, 400.0)),
+                (3, Rect::new(500.0, 400.0, 500.0, 400.0)),
+            ]
+        );
+        assert_eq!(tree.windows(), [WindowId(1), WindowId(2), WindowId(3)]);
+    }
+
+    #[test]
+    fn remove_gives_the_space_to_the_sibling() {
+        let mut tree = tree(&[1, 2, 3], None);
+        assert!(tree.remove(WindowId(2)));
+        assert!(!tree.remove(WindowId(2)));
+        assert_eq!(
+            frames(&tree, &NO_GAPS),
+            [
+                (1, Rect::new(0.0, 0.0, 500.0, 800.0)),
+                (3, Rect::new(500.0, 0.0, 500.0, 800.0)),
+            ]
+        );
+
+        assert!(tree.remove(WindowId(1)));
+        assert_eq!(frames(&tree, &NO_GAPS), [(3, FRAME)]);
+        assert!(tree.remove(WindowId(3)));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn gaps_separate_windows_and_screen_edges() {
+        let gaps = gaps(10.0, 20.0);
+        let tree = tree(&[1, 2], None);
+        assert_eq!(
+            frames(&tree, &gaps),
+            [
+                (1, Rect::new(10.0, 10.0, 480.0, 780.0)),
+                (2, Rect::new(510.0, 10.0, 480.0, 780.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_splits_the_focused_window_and_forgets_missing_ones() {
+        let descriptor = |id, focused| descriptor(id, Layout::BSP, focused);
+        let mut tree = BspTree::new();
+        tree.layout(
+            FRAME,
+            &NO_GAPS,
+            &[descriptor(1, false), descriptor(2, true)],
+        );
+
+        let frames = tree.layout(
+            FRAME,
+            &NO_GAPS,
+            &[
+                descriptor(1, false),
+                descriptor(2, true),
+                descriptor(3, false),
+            ],
+        );
+        assert_eq!(
+            frames,
+            [
+                (WindowId(1), Rect::new(0.0, 0.0, 500.0, 800.0)),
+                (WindowId(2), Rect::new(500.0, 0.0, 500.0, 400.0)),
+                (WindowId(3), Rect::new(500.0, 400.0, 500.0, 400.0)),
+            ]
+        );
+
+        let frames = tree.layout(
+            FRAME,
+            &NO_GAPS,
+            &[descriptor(2, true), descriptor(3, false)],
+        );
+        assert_eq!(
+            frames,
+            [
+                (WindowId(2), Rect::new(0.0, 0.0, 1000.0, 400.0)),
+                (WindowId(3), Rect::new(0.0, 400.0, 1000.0, 400.0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A small language for carving the screen into several named slots at once,
+//! such as:
+//!
+//! ```text
+//! hsplit(0.6, editor, vsplit(0.5, terminal, browser))
+//! ```
+//!
+//! `hsplit(ratio, left, right)` cuts an area side by side, giving `ratio` of
+//! it to `left`, and `vsplit(ratio, top, bottom)` cuts it one above the
+//! other. Every other name is a new slot covering the area it ends up in,
+//! except for `_`, which leaves its area unnamed.
+
+use snafu::Snafu;
+
+use super::{Axis, Layout, Side, Slot, Split};
+
+/// Offsets are in bytes from the start of the expression.
+#[derive(Debug, Snafu)]
+pub enum ExpressionParseError {
+    #[snafu(display("Expected {expected} but found '{found}'"))]
+    Unexpected {
+        offset: usize,
+        expected: &'static str,
+        found: char,
+    },
+    #[snafu(display("Expected {expected} but the expression ended"))]
+    UnexpectedEnd {
+        offset: usize,
+        expected: &'static str,
+    },
+    #[snafu(display("Unknown split '{name}', expected 'hsplit' or 'vsplit'"))]
+    UnknownSplit { offset: usize, name: String },
+    #[snafu(display(
+        "'{ratio}' is not a split ratio strictly between 0 and 1"
+    ))]
+    InvalidRatio { offset: usize, ratio: String },
+    #[snafu(display("Slot '{name}' appears more than once"))]
+    DuplicateName { offset: usize, name: String },
+    #[snafu(display("'{name}' is already a layout"))]
+    AlreadyDefined { offset: usize, name: String },
+}
+
+impl ExpressionParseError {
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Unexpected { offset, .. }
+            | Self::UnexpectedEnd { offset, .. }
+            | Self::UnknownSplit { offset, .. }
+            | Self::InvalidRatio { offset, .. }
+            | Self::DuplicateName { offset, .. }
+            | Self::AlreadyDefined { offset, .. } => *offset,
+        }
+    }
+}
+
+/// A name that stands for no slot.
+const UNNAMED: &str = "_";
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    /// Whether a name is taken by a layout defined outside the expression.
+    is_defined: &'a dyn Fn(&Layout) -> bool,
+    offset: usize,
+    /// Splits from the whole screen down to the current area.
+    path: Vec<Split>,
+    slots: Vec<(Layout, Slot)>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.offset..];
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, expected: &'static str) -> ExpressionParseError {
+        match self.peek() {
+            Some(found) => ExpressionParseError::Unexpected {
+                offset: self.offset,
+                expected,
+                found,
+            },
+            None => ExpressionParseError::UnexpectedEnd {
+                offset: self.offset,
+                expected,
+            },
+        }
+    }
+
+    fn expect(
+        &mut self,
+        c: char,
+        expected: &'static str,
+    ) -> Result<(), ExpressionParseError> {
+        self.skip_whitespace();
+        if self.peek() != Some(c) {
+            return Err(self.error(expected));
+        }
+        self.offset += c.len_utf8();
+        Ok(())
+    }
+
+    /// Consumes characters while `predicate` holds, returning them.
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &str {
+        let start = self.offset;
+        let rest = &self.source[start..];
+        self.offset += rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        &self.source[start..self.offset]
+    }
+
+    fn ratio(&mut self) -> Result<f64, ExpressionParseError> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let ratio = self.take_while(|c| c.is_ascii_digit() || c == '.');
+        if ratio.is_empty() {
+            return Err(self.error("a split ratio"));
+        }
+        match ratio.parse::<f64>() {
+            Ok(value) if value > 0.0 && value < 1.0 => Ok(value),
+            _ => Err(ExpressionParseError::InvalidRatio {
+                offset: start,
+                ratio: ratio.to_string(),
+            }),
+        }
+    }
+
+    fn child(&mut self, split: Split) -> Result<(), ExpressionParseError> {
+        self.path.push(split);
+        self.expression()?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<(), ExpressionParseError> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let name = self.take_while(is_name_char).to_string();
+        if name.is_empty() {
+            return Err(self.error("a slot name or split"));
+        }
+
+        self.skip_whitespace();
+        if self.peek() != Some('(') {
+            if name != UNNAMED {
+                let layout = Layout::new(name.as_str());
+                if (self.is_defined)(&layout) {
+                    return Err(ExpressionParseError::AlreadyDefined {
+                        offset: start,
+                        name,
+                    });
+                }
+                if self.slots.iter().any(|(existing, _)| *existing == layout) {
+                    return Err(ExpressionParseError::DuplicateName {
+                        offset: start,
+                        name,
+                    });
+                }
+                self.slots.push((
+                    layout,
+                    Slot {
+                        splits: self.path.clone(),
+                    },
+                ));
+            }
+            return Ok(());
+        }
+
+        let axis = match name.as_str() {
+            "hsplit" => Axis::Horizontal,
+            "vsplit" => Axis::Vertical,
+            _ => {
+                return Err(ExpressionParseError::UnknownSplit {
+                    offset: start,
+                    name,
+                });
+            }
+        };
+        self.expect('(', "'('")?;
+        let ratio = self.ratio()?;
+        self.expect(',', "','")?;
+        self.child(Split {
+            axis,
+            ratio,
+            side: Side::First,
+        })?;
+        self.expect(',', "','")?;
+        self.child(Split {
+            axis,
+            ratio,
+            side: Side::Second,
+        })?;
+        self.expect(')', "')'")
+    }
+}
+
+/// Every slot named in `source`, in the order they appear. Names that
+/// `is_defined` are rejected rather than redefined.
+pub fn parse(
+    source: &str,
+    is_defined: impl Fn(&Layout) -> bool,
+) -> Result<Vec<(Layout, Slot)>, ExpressionParseError> {
+    let mut parser = Parser {
+        source,
+        is_defined: &is_defined,
+        offset: 0,
+        path: vec![],
+        slots: vec![],
+    };
+    parser.expression()?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("the end of the expression"));
+    }
+    Ok(parser.slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::*;
+    use crate::layout::{
+        Gaps, default_slots,
+        fixtures::{FRAME, gaps},
+    };
+
+    const GAPS: Gaps = gaps(10.0, 20.0);
+
+    /// Every slot in `source` and its frame on [`FRAME`], one per line.
+    fn render(source: &str) -> String {
+        let mut rendered = String::new();
+        for (layout, slot) in parse(source, |_| false).unwrap() {
+            let frame = slot.frame(FRAME, &GAPS);
+            writeln!(
+                rendered,
+                "{} {} {} {} {}",
+                layout.name(),
+                frame.origin.x,
+                frame.origin.y,
+                frame.size.width,
+                frame.size.height,
+            )
+            .unwrap();
+        }
+        rendered
+    }
+
+    #[test]
+    fn nested_splits() {
+        assert_eq!(
+            render("hsplit(0.6, editor, vsplit(0.5, terminal, browser))"),
+            "editor 10 10 578 780\n\
+             terminal 608 10 382 380\n\
+             browser 608 410 382 380\n"
+        );
+    }
+
+    #[test]
+    fn unnamed_areas_are_skipped() {
+        assert_eq!(
+            render("vsplit(0.25, _, hsplit(0.5, left-pane, _))"),
+            "left-pane 10 215 480 575\n"
+        );
+    }
+
+    #[test]
+    fn a_single_name_covers_the_screen() {
+        assert_eq!(render("  everything  "), "everything 10 10 980 780\n");
+    }
+
+    #[test]
+    fn slots_sharing_a_cut_are_an_inner_gap_apart() {
+        let slots = parse("hsplit(0.3, a, hsplit(0.5, b, c))", |_| false)
+            .unwrap()
+            .into_iter()
+            .map(|(_, slot)| slot.frame(FRAME, &GAPS))
+            .collect::<Vec<_>>();
+        for pair in slots.windows(2) {
+            let gap = pair[1].min_x() - pair[0].max_x();
+            assert!((gap - GAPS.inner).abs() < 1e-9, "{gap}");
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let error = |source| parse(source, |_| false).unwrap_err();
+
+        let ExpressionParseError::UnknownSplit { offset, name } =
+            error("hsplit(0.5, a, split(0.5, b, c))")
+        else {
+            panic!("expected an unknown split");
+        };
+        assert_eq!((offset, name.as_str()), (15, "split"));
+
+        let ExpressionParseError::InvalidRatio { offset, .. } =
+            error("vsplit(1.5, a, b)")
+        else {
+            panic!("expected an invalid ratio");
+        };
+        assert_eq!(offset, 7);
+
+        let ExpressionParseError::DuplicateName { offset, .. } =
+            error("hsplit(0.5, a, a)")
+        else {
+            panic!("expected a duplicate name");
+        };
+        assert_eq!(offset, 15);
+
+        assert!(matches!(
+            error("hsplit(0.5, a"),
+            ExpressionParseError::UnexpectedEnd { offset: 13, .. }
+        ));
+        assert!(matches!(
+            error("hsplit(0.5, a, b) c"),
+            ExpressionParseError::Unexpected {
+                offset: 18,
+                found: 'c',
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn existing_layouts_are_not_redefined() {
+        let slots = default_slots();
+        let error = parse("hsplit(0.6, full, vsplit(0.5, a, b))", |layout| {
+            slots.contains_key(layout)
+        })
+        .unwrap_err();
+        let ExpressionParseError::AlreadyDefined { offset, name } = error
+        else {
+            panic!("expected a redefinition");
+        };
+        assert_eq!((offset, name.as_str()), (12, "full"));
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The first few windows share a column on the left, and the rest stack
+//! evenly in a column on the right, as in dwm.
+
+use super::{Gaps, LayoutAlgorithm, WindowDescriptor, forgotten};
+use crate::{
+    backend::WindowId,
+    geometry::{Insets, Rect},
+    keybind::Action,
+};
+
+/// The master column never gets narrower or wider than this fraction of the
+/// screen.
+const MIN_RATIO: f64 = 0.1;
+const MAX_RATIO: f64 = 0.9;
+
+/// How much [`Action::GrowMaster`] and [`Action::ShrinkMaster`] widen or
+/// narrow the master column by.
+const RATIO_STEP: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasterStack {
+    /// In the order they were added, masters first.
+    windows: Vec<WindowId>,
+    masters: usize,
+    /// How much of the screen the master column takes up.
+    ratio: f64,
+}
+
+impl Default for MasterStack {
+    fn default() -> Self {
+        Self::new(1, 0.55)
+    }
+}
+
+impl MasterStack {
+    /// `ratio` is clamped between 0.1 and 0.9.
+    pub fn new(masters: usize, ratio: f64) -> Self {
+        Self {
+            windows: vec![],
+            masters,
+            ratio: ratio.clamp(MIN_RATIO, MAX_RATIO),
+        }
+    }
+
+    pub fn windows(&self) -> &[WindowId] {
+        &self.windows
+    }
+
+    pub fn contains(&self, window: WindowId) -> bool {
+        self.windows.contains(&window)
+    }
+
+    pub fn masters(&self) -> usize {
+        self.masters
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Adds `window` to the bottom of the stack.
+    pub fn insert(&mut self, window: WindowId) {
+        if !self.contains(window) {
+            self.windows.push(window);
+        }
+    }
+
+    /// Returns whether `window` was laid out.
+    pub fn remove(&mut self, window: WindowId) -> bool {
+        let count = self.windows.len();
+        self.windows.retain(|existing| *existing != window);
+        self.windows.len() != count
+    }
+
+    /// Changes the number of masters by `delta`, from none at all up to every
+    /// window there is.
+    pub fn add_masters(&mut self, delta: isize) {
+        let count = self.windows.len();
+        self.masters = self
+            .masters
+            .min(count)
+            .saturating_add_signed(delta)
+            .min(count);
+    }
+
+    /// Widens the master column by `delta`, a fraction of the screen.
+    pub fn resize(&mut self, delta: f64) {
+        self.ratio = (self.ratio + delta).clamp(MIN_RATIO, MAX_RATIO);
+    }
+
+    /// The frame of every window, masters first, within `frame`, the usable
+    /// area of a screen. When every window is a master or none are, the one
+    /// column takes up the whole screen.
+    pub fn frames(&self, frame: Rect, gaps: &Gaps) -> Vec<(WindowId, Rect)> {
+        let area = frame.inset(gaps.outer);
+        let masters = self.masters.min(self.windows.len());
+        let (master_windows, stack_windows) = self.windows.split_at(masters);
+
+        let (master_area, stack_area) =
+            if master_windows.is_empty() || stack_windows.is_empty() {
+                (area, area)
+            } else {
+                let (left, right) = area.split_horizontal(self.ratio);
+                let half_gap = gaps.inner / 2.0;
+                (
+                    left.inset(Insets {
+                        right: half_gap,
+                        ..Insets::default()
+                    }),
+                    right.inset(Insets {
+                        left: half_gap,
+                        ..Insets::default()
+                    }),
+                )
+            };
+
+        column(master_area, master_windows, gaps.inner)
+            .chain(column(stack_area, stack_windows, gaps.inner))
+            .collect()
+    }
+}
+
+impl LayoutAlgorithm for MasterStack {
+    fn layout(
+        &mut self,
+        frame: Rect,
+        gaps: &Gaps,
+        windows: &[WindowDescriptor],
+    ) -> Vec<(WindowId, Rect)> {
+        for window in forgotten(&self.windows, windows) {
+            self.remove(window);
+        }
+        for window in windows {
+            self.insert(window.id);
+        }
+        self.frames(frame, gaps)
+    }
+
+    fn perform(&mut self, action: &Action) -> Option<WindowId> {
+        match action {
+            Action::AddMaster => self.add_masters(1),
+            Action::RemoveMaster => self.add_masters(-1),
+            Action::GrowMaster => self.resize(RATIO_STEP),
+            Action::ShrinkMaster => self.resize(-RATIO_STEP),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Stacks `windows` in `area` with equal heights, `gap` apart.
+fn column(
+    area: Rect,
+    windows: &[WindowId],
+    gap: f64,
+) -> impl Iterator<Item = (WindowId, Rect)> {
+    let count = windows.len() as f64;
+    let height = (area.size.height - gap * (count - 1.0)) / count;
+    windows.iter().enumerate().map(move |(index, window)| {
+        (
+            *window,
+            Rect::new(
+                area.min_x(),
+                area.min_y() + index as f64 * (height + gap),
+                area.size.width,
+                height,
+            ),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{
+        Layout,
+        fixtures::{FRAME, descriptor, gaps, numbered},
+    };
+
+    const GAPS: Gaps = gaps(0.0, 20.0);
+
+    fn with_windows(windows: u64, masters: usize, ratio: f64) -> MasterStack {
+        let mut stack = MasterStack::new(masters, ratio);
+        for window in 1..=windows {
+            stack.insert(WindowId(window));
+        }
+        stack
+    }
+
+    fn frames(stack: &MasterStack) -> Vec<(u64, Rect)> {
+        numbered(stack.frames(FRAME, &GAPS))
+    }
+
+    #[test]
+    fn masters_go_left_and_the_rest_stack_right() {
+        let stack = with_windows(3, 1, 0.5);
+        assert_eq!(
+            frames(&stack),
+            [
+                (1, Rect::new(0.0, 0.0, 490.0, 800.0)),
+                (2, Rect::new(510.0, 0.0, 490.0, 390.0)),
+                (3, Rect::new(510.0, 410.0, 490.0, 390.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn one_column_fills_the_screen() {
+        let stack = with_windows(2, 2, 0.5);
+        assert_eq!(
+            frames(&stack),
+            [
+                (1, Rect::new(0.0, 0.0, 1000.0, 390.0)),
+                (2, Rect::new(0.0, 410.0, 1000.0, 390.0)),
+            ]
+        );
+        assert_eq!(frames(&MasterStack::new(0, 0.5)), []);
+    }
+
+    #[test]
+    fn masters_are_clamped_to_the_window_count() {
+        let mut stack = with_windows(2, 1, 0.5);
+        stack.perform(&Action::AddMaster);
+        stack.perform(&Action::AddMaster);
+        assert_eq!(stack.masters(), 2);
+
+        // One press always makes a difference.
+        stack.perform(&Action::RemoveMaster);
+        assert_eq!(stack.masters(), 1);
+        stack.perform(&Action::RemoveMaster);
+        stack.perform(&Action::RemoveMaster);
+        assert_eq!(stack.masters(), 0);
+
+        // Closing windows can leave more masters than windows.
+        let mut stack = with_windows(2, 5, 0.5);
+        stack.perform(&Action::RemoveMaster);
+        assert_eq!(stack.masters(), 1);
+    }
+
+    #[test]
+    fn the_master_column_is_resized_within_bounds() {
+        let mut stack = with_windows(2, 1, 0.5);
+        stack.perform(&Action::GrowMaster);
+        assert_eq!(
+            frames(&stack),
+            [
+                (1, Rect::new(0.0, 0.0, 540.0, 800.0)),
+                (2, Rect::new(560.0, 0.0, 440.0, 800.0)),
+            ]
+        );
+
+        for _ in 0..20 {
+            stack.perform(&Action::ShrinkMaster);
+        }
+        assert_eq!(stack.ratio(), MIN_RATIO);
+        assert_eq!(MasterStack::new(1, 2.0).ratio(), MAX_RATIO);
+    }
+
+    #[test]
+    fn layout_forgets_missing_windows() {
+        let descriptor = |id| descriptor(id, Layout::MASTER_STACK, false);
+        let mut stack = MasterStack::new(1, 0.5);
+        stack.layout(FRAME, &GAPS, &[descriptor(1), descriptor(2)]);
+        let frames = stack.layout(FRAME, &GAPS, &[descriptor(2)]);
+        assert_eq!(frames, [(WindowId(2), FRAME)]);
+        assert_eq!(stack.windows(), [WindowId(2)]);
+    }
+}
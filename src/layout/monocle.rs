@@ -0,0 +1,195 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Every window takes up the whole screen, stacked on top of each other, with
+//! one of them raised at a time.
+
+use super::{
+    Gaps, LayoutAlgorithm, StackPosition, WindowDescriptor, forgotten,
+};
+use crate::{backend::WindowId, geometry::Rect, keybind::Action};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Monocle {
+    /// In the order they were added.
+    windows: Vec<WindowId>,
+    /// The index of the raised window.
+    raised: usize,
+}
+
+impl Monocle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn windows(&self) -> &[WindowId] {
+        &self.windows
+    }
+
+    pub fn contains(&self, window: WindowId) -> bool {
+        self.windows.contains(&window)
+    }
+
+    /// Returns `None` if there are no windows.
+    pub fn raised(&self) -> Option<WindowId> {
+        self.windows.get(self.raised).copied()
+    }
+
+    pub fn position(&self, window: WindowId) -> Option<StackPosition> {
+        let index = self.windows.iter().position(|w| *w == window)?;
+        Some(StackPosition {
+            index,
+            count: self.windows.len(),
+        })
+    }
+
+    /// Adds `window` to the bottom of the stack.
+    pub fn insert(&mut self, window: WindowId) {
+        if !self.contains(window) {
+            self.windows.push(window);
+        }
+    }
+
+    /// Returns whether `window` was in the stack. Removing the raised window
+    /// raises the next one.
+    pub fn remove(&mut self, window: WindowId) -> bool {
+        let Some(index) = self.windows.iter().position(|w| *w == window) else {
+            return false;
+        };
+        self.windows.remove(index);
+        if index < self.raised {
+            self.raised -= 1;
+        }
+        if self.raised >= self.windows.len() {
+            self.raised = 0;
+        }
+        true
+    }
+
+    /// Raises the window `delta` places further down the stack, wrapping
+    /// around at either end, and returns it.
+    pub fn cycle(&mut self, delta: isize) -> Option<WindowId> {
+        if self.windows.is_empty() {
+            return None;
+        }
+        self.raised = (self.raised as isize + delta)
+            .rem_euclid(self.windows.len() as isize)
+            as usize;
+        self.raised()
+    }
+
+    /// The frame of every window, which is all of `frame`, the usable area
+    /// of a screen, within the outer gaps.
+    pub fn frames(&self, frame: Rect, gaps: &Gaps) -> Vec<(WindowId, Rect)> {
+        let area = frame.inset(gaps.outer);
+        self.windows.iter().map(|window| (*window, area)).collect()
+    }
+}
+
+impl LayoutAlgorithm for Monocle {
+    fn layout(
+        &mut self,
+        frame: Rect,
+        gaps: &Gaps,
+        windows: &[WindowDescriptor],
+    ) -> Vec<(WindowId, Rect)> {
+        for window in forgotten(&self.windows, windows) {
+            self.remove(window);
+        }
+        for window in windows {
+            self.insert(window.id);
+        }
+        self.frames(frame, gaps)
+    }
+
+    fn perform(&mut self, action: &Action) -> Option<WindowId> {
+        match action {
+            Action::CycleMonocle => self.cycle(1),
+            _ => None,
+        }
+    }
+
+    fn stack_position(&self, window: WindowId) -> Option<StackPosition> {
+        self.position(window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(windows: &[u64]) -> Monocle {
+        let mut monocle = Monocle::new();
+        for window in windows {
+            monocle.insert(WindowId(*window));
+        }
+        monocle
+    }
+
+    #[test]
+    fn cycle_wraps_around_either_end() {
+        let mut monocle = stack(&[1, 2, 3]);
+        assert_eq!(monocle.raised(), Some(WindowId(1)));
+        assert_eq!(monocle.cycle(1), Some(WindowId(2)));
+        assert_eq!(monocle.cycle(2), Some(WindowId(1)));
+        assert_eq!(monocle.cycle(-1), Some(WindowId(3)));
+        assert_eq!(
+            monocle.position(WindowId(3)),
+            Some(StackPosition { index: 2, count: 3 })
+        );
+    }
+
+    #[test]
+    fn removing_the_raised_window_raises_the_next() {
+        let mut monocle = stack(&[1, 2, 3]);
+        monocle.cycle(1);
+        assert!(monocle.remove(WindowId(2)));
+        assert_eq!(monocle.raised(), Some(WindowId(3)));
+
+        // The last one wraps around to the first.
+        assert!(monocle.remove(WindowId(3)));
+        assert_eq!(monocle.raised(), Some(WindowId(1)));
+        assert!(!monocle.remove(WindowId(3)));
+        assert_eq!(monocle.windows(), [WindowId(1)]);
+    }
+
+    #[test]
+    fn removing_a_window_below_keeps_the_raised_one() {
+        let mut monocle = stack(&[1, 2, 3]);
+        monocle.cycle(2);
+        monocle.remove(WindowId(1));
+        assert_eq!(monocle.raised(), Some(WindowId(3)));
+        assert_eq!(
+            monocle.position(WindowId(3)),
+            Some(StackPosition { index: 1, count: 2 })
+        );
+    }
+
+    #[test]
+    fn one_window_stays_raised() {
+        let mut monocle = stack(&[1]);
+        assert_eq!(monocle.cycle(1), Some(WindowId(1)));
+        assert_eq!(monocle.cycle(-1), Some(WindowId(1)));
+        assert_eq!(
+            monocle
+                .position(WindowId(1))
+                .map(|position| position.to_string()),
+            Some("1/1".into())
+        );
+
+        monocle.remove(WindowId(1));
+        assert_eq!(monocle.cycle(1), None);
+        assert_eq!(monocle.position(WindowId(1)), None);
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Every window is a full-height column on a strip that goes on forever to
+//! the right, and the screen shows a few columns of it at a time, as in niri
+//! or PaperWM.
+
+use super::{Gaps, LayoutAlgorithm, WindowDescriptor, forgotten};
+use crate::{backend::WindowId, geometry::Rect, keybind::Action};
+
+/// Column widths within this distance of a whole fraction of the screen
+/// count as that fraction, so that a third still fits three times.
+const WIDTH_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strip {
+    /// From left to right.
+    windows: Vec<WindowId>,
+    /// How much of the screen each column takes up, between 0 and 1.
+    column_width: f64,
+    /// The index of the leftmost column on screen.
+    scroll: usize,
+    /// The focused window that was last scrolled to.
+    focused: Option<WindowId>,
+    /// Every screen together, which columns are parked outside of so they
+    /// don't show up on another screen.
+    bounds: Option<Rect>,
+}
+
+impl Default for Strip {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl Strip {
+    /// `column_width` is clamped between 0.1 and 1.
+    pub fn new(column_width: f64) -> Self {
+        Self {
+            windows: vec![],
+            column_width: column_width.clamp(0.1, 1.0),
+            scroll: 0,
+            focused: None,
+            bounds: None,
+        }
+    }
+
+    pub fn windows(&self) -> &[WindowId] {
+        &self.windows
+    }
+
+    pub fn contains(&self, window: WindowId) -> bool {
+        self.windows.contains(&window)
+    }
+
+    /// How many columns fit on screen at once.
+    pub fn visible_columns(&self) -> usize {
+        ((1.0 / self.column_width + WIDTH_EPSILON).floor() as usize).max(1)
+    }
+
+    /// Adds `window` as the rightmost column.
+    pub fn insert(&mut self, window: WindowId) {
+        if !self.contains(window) {
+            self.windows.push(window);
+        }
+    }
+
+    /// Returns whether `window` was on the strip.
+    pub fn remove(&mut self, window: WindowId) -> bool {
+        let Some(index) = self.windows.iter().position(|w| *w == window) else {
+            return false;
+        };
+        self.windows.remove(index);
+        if index < self.scroll {
+            self.scroll -= 1;
+        }
+        self.scroll = self.scroll.min(self.windows.len().saturating_sub(1));
+        true
+    }
+
+    /// Scrolls as little as possible to bring `window` on screen, returning
+    /// whether the strip moved.
+    pub fn focus(&mut self, window: WindowId) -> bool {
+        let Some(index) = self.windows.iter().position(|w| *w == window) else {
+            return false;
+        };
+        let previous = self.scroll;
+        if index < self.scroll {
+            self.scroll = index;
+        } else if index >= self.scroll + self.visible_columns() {
+            self.scroll = index + 1 - self.visible_columns();
+        }
+        self.scroll != previous
+    }
+
+    /// Scrolls by `delta` columns, but never past either end of the strip.
+    /// Returns whether the strip moved.
+    pub fn scroll(&mut self, delta: isize) -> bool {
+        let previous = self.scroll;
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(delta)
+            .min(self.windows.len().saturating_sub(1));
+        self.scroll != previous
+    }
+
+    /// The frame of every window, from left to right, within `frame`, the
+    /// usable area of a screen. Columns that are not on screen are parked
+    /// just past the left or right edge of every screen, or of `frame` if
+    /// [`LayoutAlgorithm::set_bounds`] was never called.
+    pub fn frames(&self, frame: Rect, gaps: &Gaps) -> Vec<(WindowId, Rect)> {
+        let area = frame.inset(gaps.outer);
+        let bounds = self.bounds.unwrap_or(frame);
+        // Chosen so that columns `1 / column_width` wide fit exactly.
+        let width =
+            (area.size.width + gaps.inner) * self.column_width - gaps.inner;
+        let visible = self.scroll..self.scroll + self.visible_columns();
+
+        self.windows
+            .iter()
+            .enumerate()
+            .map(|(index, window)| {
+                let x = if index < visible.start {
+                    bounds.min_x() - width
+                } else if index >= visible.end {
+                    bounds.max_x()
+                } else {
+                    area.min_x()
+                        + (index - visible.start) as f64 * (width + gaps.inner)
+                };
+                (*window, Rect::new(x, area.min_y(), width, area.size.height))
+            })
+            .collect()
+    }
+}
+
+impl LayoutAlgorithm for Strip {
+    /// Scrolls to the focused window whenever that changes, so scrolling by
+    /// hand sticks until then.
+    fn layout(
+        &mut self,
+        frame: Rect,
+        gaps: &Gaps,
+        windows: &[WindowDescriptor],
+    ) -> Vec<(WindowId, Rect)> {
+        for window in forgotten(&self.windows, windows) {
+            self.remove(window);
+        }
+        for window in windows {
+            self.insert(window.id);
+        }
+
+        let focused = self.windows.iter().copied().find(|column| {
+            windows
+                .iter()
+                .any(|window| window.id == *column && window.focused)
+        });
+        if focused != self.focused {
+            self.focused = focused;
+            if let Some(window) = focused {
+                self.focus(window);
+            }
+        }
+
+        self.frames(frame, gaps)
+    }
+
+    fn perform(&mut self, action: &Action) -> Option<WindowId> {
+        match action {
+            Action::ScrollLeft => {
+                self.scroll(-1);
+            }
+            Action::ScrollRight => {
+                self.scroll(1);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn set_bounds(&mut self, bounds: Rect) {
+        self.bounds = Some(bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixtures::{FRAME, NO_GAPS};
+
+    fn strip(windows: u64) -> Strip {
+        let mut strip = Strip::new(0.5);
+        for window in 1..=windows {
+            strip.insert(WindowId(window));
+        }
+        strip
+    }
+
+    fn xs(strip: &Strip) -> Vec<f64> {
+        strip
+            .frames(FRAME, &NO_GAPS)
+            .into_iter()
+            .map(|(_, frame)| frame.min_x())
+            .collect()
+    }
+
+    #[test]
+    fn columns_past_the_screen_are_parked_beside_it() {
+        let mut strip = strip(4);
+        assert_eq!(xs(&strip), [0.0, 500.0, 1000.0, 1000.0]);
+
+        assert!(strip.scroll(1));
+        assert_eq!(xs(&strip), [-500.0, 0.0, 500.0, 1000.0]);
+    }
+
+    #[test]
+    fn parked_columns_stay_off_every_screen() {
+        let mut strip = strip(4);
+        strip.scroll(1);
+        // Another screen on each side.
+        strip.set_bounds(Rect::new(-1200.0, -100.0, 3400.0, 1000.0));
+        assert_eq!(xs(&strip), [-1700.0, 0.0, 500.0, 2200.0]);
+    }
+
+    #[test]
+    fn scrolling_stops_at_either_end() {
+        let mut strip = strip(3);
+        assert!(!strip.scroll(-1));
+        assert!(strip.scroll(5));
+        assert_eq!(xs(&strip), [-500.0, -500.0, 0.0]);
+        assert!(!strip.scroll(1));
+    }
+
+    #[test]
+    fn focusing_scrolls_as_little_as_possible() {
+        let mut strip = strip(5);
+        assert!(!strip.focus(WindowId(2)));
+        assert!(strip.focus(WindowId(4)));
+        assert_eq!(xs(&strip), [-500.0, -500.0, 0.0, 500.0, 1000.0]);
+        assert!(strip.focus(WindowId(1)));
+        assert_eq!(xs(&strip), [0.0, 500.0, 1000.0, 1000.0, 1000.0]);
+    }
+
+    #[test]
+    fn removing_a_column_keeps_the_view() {
+        let mut strip = strip(4);
+        strip.scroll(2);
+        assert!(strip.remove(WindowId(1)));
+        assert_eq!(xs(&strip), [-500.0, 0.0, 500.0]);
+    }
+}
@@ -12,27 +12,47 @@
 // You should have received a copy of the GNU General Public License along with
 // this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{error::Error, fmt, ptr};
+#[cfg(target_os = "macos")]
+use std::ptr;
+use std::{error::Error, fmt};
 
+#[cfg(target_os = "macos")]
 use accessibility_sys::{
     AXError, AXIsProcessTrustedWithOptions, kAXErrorSuccess,
     kAXTrustedCheckOptionPrompt,
 };
+use backend::WindowId;
+#[cfg(target_os = "macos")]
 use cocoa::{
     appkit::NSRunningApplication,
     base::nil,
     foundation::{NSArray, NSString},
 };
+#[cfg(target_os = "macos")]
 use core_foundation_sys::{
     base::CFTypeRef, dictionary::CFDictionaryCreate, number::kCFBooleanTrue,
 };
+#[cfg(target_os = "macos")]
 use memory::{CopyOnWrite, ManageWithRc, Rc};
+#[cfg(target_os = "macos")]
 use rdev::ListenError;
 use snafu::Snafu;
+#[cfg(target_os = "macos")]
 use wrappers::App;
 
+pub mod backend;
+pub mod config;
+pub mod coordinates;
+pub mod debounce;
+pub mod geometry;
+pub mod keybind;
 pub mod layout;
+#[cfg(target_os = "macos")]
 pub mod memory;
+pub mod reducer;
+pub mod rules;
+pub mod trace;
+#[cfg(target_os = "macos")]
 pub mod wrappers;
 
 #[derive(Debug, Snafu)]
@@ -43,10 +63,16 @@ pub enum UnnamedError {
     CouldNotCreateCFObject,
     #[snafu(display("Apple API object was unexpectedly null"))]
     UnexpectedNull,
+    #[cfg(target_os = "macos")]
     #[snafu(display("Accessibility API error: {:?} ({code})", code.discussion()))]
     AXError { code: AXError },
+    #[cfg(target_os = "macos")]
     #[snafu(display("rdev error: {inner:?}"))]
     RDevError { inner: ListenError },
+    #[snafu(display("No app with PID {pid}"))]
+    UnknownApp { pid: i32 },
+    #[snafu(display("No window with ID {}", window.0))]
+    UnknownWindow { window: WindowId },
     #[snafu(whatever, display("{message}"))]
     Whatever {
         message: String,
@@ -55,12 +81,14 @@ pub enum UnnamedError {
     },
 }
 
+#[cfg(target_os = "macos")]
 pub trait AXErrorExt {
     fn discussion(&self) -> Option<&'static str>;
 
     fn into_result(self) -> Result<(), UnnamedError>;
 }
 
+#[cfg(target_os = "macos")]
 impl AXErrorExt for AXError {
     fn discussion(&self) -> Option<&'static str> {
         use accessibility_sys::*;
@@ -145,6 +173,8 @@ pub struct BundleID<'a>(&'a str);
 
 #[derive(Debug, Snafu)]
 pub enum BundleIDParseError {
+    #[snafu(display("Empty bundle ID"))]
+    Empty,
     #[snafu(display("Invalid character '{c}' at index {index} in bundle ID"))]
     InvalidCharacter { index: usize, c: char },
 }
@@ -153,7 +183,9 @@ impl<'a> TryFrom<&'a str> for BundleID<'a> {
     type Error = BundleIDParseError;
 
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
-        if let Some((problem_index, problem_char)) =
+        if value.is_empty() {
+            Err(BundleIDParseError::Empty)
+        } else if let Some((problem_index, problem_char)) =
             value.char_indices().find(|(_, c)| {
                 !(c.is_ascii_alphanumeric() || *c == '-' || *c == '.')
             })
@@ -180,6 +212,7 @@ impl fmt::Display for BundleID<'_> {
     }
 }
 
+#[cfg(target_os = "macos")]
 pub fn has_accessibility_permissions() -> Result<bool, UnnamedError> {
     // SAFETY: `kAXTrustedCheckOptionPrompt` should be initialized by
     // CoreFoundation.
@@ -210,6 +243,7 @@ pub fn has_accessibility_permissions() -> Result<bool, UnnamedError> {
     Ok(is_trusted)
 }
 
+#[cfg(target_os = "macos")]
 pub fn running_apps_with_bundle_id(
     bundle_id: BundleID,
 ) -> Result<Box<[App<'_>]>, UnnamedError> {
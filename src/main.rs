@@ -12,261 +12,412 @@
 // You should have received a copy of the GNU General Public License along with
 // this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{
-    collections::HashSet,
-    ffi, fs,
-    path::PathBuf,
-    ptr::{self},
-    sync::LazyLock,
-};
+#[cfg(target_os = "macos")]
+use std::{cell::RefCell, collections::HashSet, fs::File, io::BufWriter};
+use std::{fs, path::PathBuf};
 
-use accessibility_sys::{
-    AXObserverAddNotification, AXObserverCreate, AXObserverGetRunLoopSource,
-    AXObserverRef, AXUIElementRef, kAXWindowMovedNotification,
-    kAXWindowResizedNotification,
-};
 use argh::FromArgs;
-use cocoa::{appkit::NSWorkspace, base::nil};
-use core_foundation_sys::{
-    runloop::{CFRunLoopAddSource, CFRunLoopGetCurrent, kCFRunLoopDefaultMode},
-    string::CFStringRef,
-};
-use dashmap::DashMap;
-use rdev::{EventType, Key};
+#[cfg(target_os = "macos")]
+use rdev::EventType;
 use snafu::{ResultExt, whatever};
+#[cfg(target_os = "macos")]
 use unnamed::{
-    AXErrorExt, BundleID, UnnamedError, has_accessibility_permissions,
-    layout::{Layout, Layouts, get_layouts},
-    memory::{CopyOnWrite, Unique},
-    running_apps_with_bundle_id,
-    wrappers::{
-        AccessibilityElement, App, Window, create_cfstring_from_static_str,
-    },
+    BundleID,
+    backend::{BackendEvent, WindowBackend, macos::MacosBackend},
+    has_accessibility_permissions,
+    keybind::{Action, Chord, Key, Modifiers},
+    layout::get_screens,
+    reducer::{Event, State, reduce},
+    trace::{Recorder, TraceEntry},
 };
+use unnamed::{UnnamedError, config::Config, geometry::Rect, trace};
+
+#[cfg(target_os = "macos")]
+struct Daemon {
+    backend: MacosBackend,
+    state: State,
+    recorder: Option<Recorder<BufWriter<File>>>,
+    config: Config,
+    config_path: PathBuf,
+}
 
-static LAYOUT_ASSIGNMENTS: LazyLock<DashMap<String, (Layout, bool)>> =
-    LazyLock::new(DashMap::new);
-
-unsafe extern "C" fn observer_callback(
-    _observer: AXObserverRef,
-    element: AXUIElementRef,
-    _notification: CFStringRef,
-    refcon: *mut ffi::c_void,
-) {
-    // SAFETY: todo
-    let layouts = unsafe { (refcon as *const _ as *const Layouts).as_ref() }
-        .expect("Got passed null?");
-    //println!("resize: {element:?} {_notification:?}");
-
-    //println!("tryign to print");
-    // SAFETY: todo
-    //println!("{}", unsafe { CFGetRetainCount(element as CFTypeRef) });
-
-    // SAFETY: todo
-    let mut window = unsafe { Window::borrow_inner(element) }
-        .expect("Window observer should be passed valid window");
-
-    let (layout, enabled) =
-        *LAYOUT_ASSIGNMENTS.get(window.bundle_id().as_ref()).unwrap();
-    if enabled {
-        window
-            .relayout(&layouts.rects[layout as usize])
-            .expect("Failed to relayout window");
+#[cfg(target_os = "macos")]
+impl Daemon {
+    fn record(&mut self, entry: &TraceEntry) {
+        if let Some(recorder) = &mut self.recorder
+            && let Err(error) = recorder.record(entry)
+        {
+            eprintln!("error: {error}");
+        }
     }
-}
 
-#[derive(Default)]
-struct KeyState {
-    keys_down: HashSet<Key>,
-}
+    fn dispatch(&mut self, event: Event) {
+        self.record(&TraceEntry::Event(event.clone()));
+        for command in reduce(&mut self.state, event) {
+            match self.backend.execute(&command) {
+                Ok(()) => self.record(&TraceEntry::Applied(command)),
+                Err(error) => eprintln!("error: {error}"),
+            }
+        }
+    }
 
-impl KeyState {
-    fn press(&mut self, key: Key) {
-        self.keys_down.insert(key);
+    /// Lays every window out again on the screens as they are now.
+    fn refresh_screens(&mut self) -> Result<(), UnnamedError> {
+        let screens = get_screens(&self.config)
+            .whatever_context("Failed to compute layouts")?;
+        self.dispatch(Event::ScreensChanged(screens));
+        Ok(())
     }
 
-    fn release(&mut self, key: &Key) {
-        self.keys_down.remove(key);
+    /// Keeps the current config if the file no longer loads, so a typo
+    /// can be fixed and reloaded again without restarting.
+    fn reload_config(&mut self) -> Result<(), UnnamedError> {
+        match Config::load(&self.config_path) {
+            Ok(config) => self.config = config,
+            Err(error) => {
+                eprintln!("error: {}", snafu::Report::from_error(error));
+                return Ok(());
+            }
+        }
+        self.refresh_screens()
     }
 
-    fn is_modifier_down(&self) -> bool {
-        let command = self.keys_down.contains(&Key::MetaLeft)
-            || self.keys_down.contains(&Key::MetaRight);
-        let control = self.keys_down.contains(&Key::ControlLeft)
-            || self.keys_down.contains(&Key::ControlRight);
-        let option = self.keys_down.contains(&Key::Alt)
-            || self.keys_down.contains(&Key::AltGr);
-        let shift = self.keys_down.contains(&Key::ShiftLeft)
-            || self.keys_down.contains(&Key::ShiftRight);
-        command && control && option && shift
+    /// Lets the state know about the frontmost app before acting on `chord`.
+    fn dispatch_chord(&mut self, chord: Chord) -> Result<(), UnnamedError> {
+        match self.state.action(&chord) {
+            None => return Ok(()),
+            Some(Action::ReloadConfig) => return self.reload_config(),
+            Some(_) => {}
+        }
+
+        if let Some(app) = self.backend.focused_app()? {
+            let windows = self.backend.window_infos(&app)?;
+            let focused_window = self.backend.focused_window(&app)?;
+            self.dispatch(Event::AppFocused {
+                app,
+                windows,
+                focused_window,
+            });
+        }
+        self.dispatch(Event::KeyChord(chord));
+
+        Ok(())
     }
 
-    fn is_modified(&self, key: Key) -> bool {
-        self.is_modifier_down() && self.keys_down.contains(&key)
+    fn dispatch_backend_event(
+        &mut self,
+        event: BackendEvent,
+    ) -> Result<(), UnnamedError> {
+        let event = match event {
+            BackendEvent::WindowMoved(window) => Event::WindowMoved {
+                window,
+                frame: self.backend.frame(window)?,
+            },
+            BackendEvent::WindowResized(window) => Event::WindowResized {
+                window,
+                frame: self.backend.frame(window)?,
+            },
+            BackendEvent::WindowClosed(window) => {
+                self.backend.forget(window);
+                Event::WindowClosed { window }
+            }
+            BackendEvent::DisplaysChanged => return self.refresh_screens(),
+        };
+        self.dispatch(event);
+        Ok(())
     }
 }
 
-fn update_layout_for_focused_window(
-    new_layout: Option<Layout>,
-    layouts: &Layouts,
-) -> Result<(), UnnamedError> {
-    // SAFETY: todo
-    let workspace = unsafe { NSWorkspace::sharedWorkspace(nil) };
-    if workspace.is_null() {
-        return Err(UnnamedError::UnexpectedNull);
-    }
+#[cfg(target_os = "macos")]
+thread_local! {
+    /// Both the accessibility observers and `rdev` run on the main thread's
+    /// run loop, so this is only ever touched from there.
+    static DAEMON: RefCell<Option<Daemon>> = const { RefCell::new(None) };
+}
 
-    // SAFETY: todo
-    let app = unsafe { NSWorkspace::frontmostApplication(workspace) };
-    if app.is_null() {
-        return Err(UnnamedError::UnexpectedNull);
-    }
+#[cfg(target_os = "macos")]
+fn on_backend_event(event: BackendEvent) {
+    DAEMON.with_borrow_mut(|daemon| {
+        if let Err(error) = daemon
+            .as_mut()
+            .expect("The run loop only starts after the daemon is created")
+            .dispatch_backend_event(event)
+        {
+            eprintln!("error: {error}");
+        }
+    });
+}
 
-    // SAFETY: todo
-    let app = unsafe { App::from_nsapp(CopyOnWrite::Borrowed(app), None) }?;
+#[cfg(target_os = "macos")]
+#[derive(Default)]
+struct KeyState {
+    keys_down: HashSet<rdev::Key>,
+}
 
-    if !LAYOUT_ASSIGNMENTS.contains_key(app.bundle_id().as_ref()) {
-        LAYOUT_ASSIGNMENTS
-            .insert(app.bundle_id().to_string(), (Layout::Full, false));
+#[cfg(target_os = "macos")]
+impl KeyState {
+    fn press(&mut self, key: rdev::Key) {
+        self.keys_down.insert(key);
     }
 
-    //println!("{}", app.bundle_id().as_ref());
-    if let Some(new_layout) = new_layout {
-        *LAYOUT_ASSIGNMENTS
-            .get_mut(app.bundle_id().as_ref())
-            .expect("We just initialized it if it didn't exist") =
-            (new_layout, true);
-    } else {
-        LAYOUT_ASSIGNMENTS
-            .get_mut(app.bundle_id().as_ref())
-            .expect("We just initialized it if it didn't exist")
-            .1 ^= true;
+    fn release(&mut self, key: &rdev::Key) {
+        self.keys_down.remove(key);
     }
 
-    for mut window in app.get_windows()? {
-        // TODO: code duplication
-        let (layout, enabled) =
-            *LAYOUT_ASSIGNMENTS.get(window.bundle_id().as_ref()).unwrap();
-        if enabled {
-            if let Err(error) = window.relayout(&layouts.rects[layout as usize])
-            {
-                eprintln!("error: {error}");
-            }
+    fn modifiers(&self) -> Modifiers {
+        use rdev::Key;
+
+        Modifiers {
+            command: self.keys_down.contains(&Key::MetaLeft)
+                || self.keys_down.contains(&Key::MetaRight),
+            control: self.keys_down.contains(&Key::ControlLeft)
+                || self.keys_down.contains(&Key::ControlRight),
+            option: self.keys_down.contains(&Key::Alt)
+                || self.keys_down.contains(&Key::AltGr),
+            shift: self.keys_down.contains(&Key::ShiftLeft)
+                || self.keys_down.contains(&Key::ShiftRight),
         }
     }
+}
 
-    Ok(())
+/// Returns `None` for modifiers and keys that cannot be bound.
+#[cfg(target_os = "macos")]
+fn chord_key(key: rdev::Key) -> Option<Key> {
+    use rdev::Key as K;
+
+    Some(match key {
+        K::KeyA => Key::Char('a'),
+        K::KeyB => Key::Char('b'),
+        K::KeyC => Key::Char('c'),
+        K::KeyD => Key::Char('d'),
+        K::KeyE => Key::Char('e'),
+        K::KeyF => Key::Char('f'),
+        K::KeyG => Key::Char('g'),
+        K::KeyH => Key::Char('h'),
+        K::KeyI => Key::Char('i'),
+        K::KeyJ => Key::Char('j'),
+        K::KeyK => Key::Char('k'),
+        K::KeyL => Key::Char('l'),
+        K::KeyM => Key::Char('m'),
+        K::KeyN => Key::Char('n'),
+        K::KeyO => Key::Char('o'),
+        K::KeyP => Key::Char('p'),
+        K::KeyQ => Key::Char('q'),
+        K::KeyR => Key::Char('r'),
+        K::KeyS => Key::Char('s'),
+        K::KeyT => Key::Char('t'),
+        K::KeyU => Key::Char('u'),
+        K::KeyV => Key::Char('v'),
+        K::KeyW => Key::Char('w'),
+        K::KeyX => Key::Char('x'),
+        K::KeyY => Key::Char('y'),
+        K::KeyZ => Key::Char('z'),
+        K::Num0 => Key::Char('0'),
+        K::Num1 => Key::Char('1'),
+        K::Num2 => Key::Char('2'),
+        K::Num3 => Key::Char('3'),
+        K::Num4 => Key::Char('4'),
+        K::Num5 => Key::Char('5'),
+        K::Num6 => Key::Char('6'),
+        K::Num7 => Key::Char('7'),
+        K::Num8 => Key::Char('8'),
+        K::Num9 => Key::Char('9'),
+        K::Space => Key::Space,
+        K::Return => Key::Return,
+        K::Tab => Key::Tab,
+        K::LeftArrow => Key::Left,
+        K::RightArrow => Key::Right,
+        K::UpArrow => Key::Up,
+        K::DownArrow => Key::Down,
+        _ => return None,
+    })
 }
 
-/// Ethan's custom macOS window layout engine.
+/// Ethan's custom macOS window layout engine. Manages windows until
+/// interrupted unless given a subcommand.
 #[derive(FromArgs)]
 struct Args {
-    /// file containing on each line a bundle ID, a comment starting wtih `#`,
-    /// or whitespace.
+    /// TOML config file, or a file containing on each line a bundle ID, a
+    /// comment starting wtih `#`, or whitespace.
+    #[argh(positional)]
+    config: Option<PathBuf>,
+
+    /// write every event and every frame applied to this file as JSON lines.
+    #[argh(option)]
+    record: Option<PathBuf>,
+
+    #[argh(subcommand)]
+    subcommand: Option<Subcommand>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Subcommand {
+    Replay(ReplayArgs),
+    Layouts(LayoutsArgs),
+}
+
+/// What to manage windows with when not given a subcommand.
+struct RunArgs {
+    config: PathBuf,
+    #[cfg_attr(not(target_os = "macos"), expect(dead_code))]
+    record: Option<PathBuf>,
+}
+
+/// Replay a trace written with `--record` against fake windows and report
+/// every event where the frames differ.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "replay")]
+struct ReplayArgs {
+    /// trace file to replay.
     #[argh(positional)]
-    bundle_id_list_file: PathBuf,
+    trace: PathBuf,
+}
+
+/// Print the frame of every layout in a config file on a screen of the given
+/// size, without touching any windows.
+#[derive(FromArgs)]
+#[argh(subcommand, name = "layouts")]
+struct LayoutsArgs {
+    /// config file, as for managing windows.
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// screen width in points.
+    #[argh(option, default = "1440.0")]
+    width: f64,
+
+    /// screen height in points, not counting the menu bar.
+    #[argh(option, default = "860.0")]
+    height: f64,
 }
 
 #[snafu::report]
 fn main() -> Result<(), UnnamedError> {
     let args: Args = argh::from_env();
 
-    let file_contents = fs::read_to_string(&args.bundle_id_list_file)
-        .whatever_context(format!(
-            "Failed to read {} as a string",
-            args.bundle_id_list_file.display()
-        ))?;
-    let bundle_ids: Vec<BundleID> = file_contents
-        .lines()
-        .filter(|line| {
-            !(line.starts_with("#") || line.chars().all(|c| c.is_whitespace()))
-        })
-        .map(BundleID::try_from)
-        .collect::<Result<Vec<_>, _>>()
-        .whatever_context("Failed to parse all given bundle IDs")?;
+    match args.subcommand {
+        Some(Subcommand::Replay(args)) => replay(args),
+        Some(Subcommand::Layouts(args)) => print_layouts(args),
+        None => {
+            let Some(config) = args.config else {
+                whatever!("Expected a config file or a subcommand");
+            };
+            run(RunArgs {
+                config,
+                record: args.record,
+            })
+        }
+    }
+}
 
-    if !has_accessibility_permissions()? {
-        whatever!("This program needs accessibility permissions to work");
+fn print_layouts(args: LayoutsArgs) -> Result<(), UnnamedError> {
+    let config = Config::load(&args.config)?;
+    let layouts = config.layouts(Rect::new(0.0, 0.0, args.width, args.height));
+
+    let name_width = layouts
+        .slots
+        .keys()
+        .map(|layout| layout.name().len())
+        .max()
+        .unwrap_or(0);
+    for (layout, rect) in layouts.rects() {
+        println!(
+            "{:name_width$}  x {:>7.1}  y {:>7.1}  w {:>7.1}  h {:>7.1}",
+            layout.name(),
+            rect.origin.x,
+            rect.origin.y,
+            rect.size.width,
+            rect.size.height,
+        );
     }
 
-    let layouts =
-        get_layouts().whatever_context("Failed to compute layouts")?;
+    Ok(())
+}
 
-    let mut observers = vec![];
+fn replay(args: ReplayArgs) -> Result<(), UnnamedError> {
+    let trace = fs::read_to_string(&args.trace).whatever_context(format!(
+        "Failed to read {} as a string",
+        args.trace.display()
+    ))?;
 
-    for bundle_id in bundle_ids {
-        LAYOUT_ASSIGNMENTS.insert(bundle_id.to_string(), (Layout::Full, true));
+    let mismatches = trace::replay(&trace)?;
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+    if !mismatches.is_empty() {
+        whatever!("{} recorded events replayed differently", mismatches.len());
+    }
 
-        for app in running_apps_with_bundle_id(bundle_id)? {
-            let mut observer = ptr::null_mut();
-            // SAFETY: todo
-            unsafe {
-                AXObserverCreate(app.pid(), observer_callback, &mut observer)
-            }
-            .into_result()?;
-            // SAFETY: todo
-            let observer = unsafe { Unique::new_mut(observer) }
-                .ok_or(UnnamedError::UnexpectedNull)?;
-
-            for mut window in app.get_windows()? {
-                window.relayout(&layouts.rects[Layout::Full as usize])?;
-
-                let notification = create_cfstring_from_static_str(
-                    kAXWindowResizedNotification,
-                )?;
-
-                // SAFETY: todo
-                unsafe {
-                    AXObserverAddNotification(
-                        observer.get(),
-                        window.inner(),
-                        notification.get(),
-                        &layouts as *const _ as *mut _,
-                    )
-                }
-                .into_result()
-                .whatever_context(format!(
-                    "Failed to observe window resizes in {bundle_id}"
-                ))?;
-
-                let notification = create_cfstring_from_static_str(
-                    kAXWindowMovedNotification,
-                )?;
-
-                // SAFETY: todo
-                unsafe {
-                    AXObserverAddNotification(
-                        observer.get(),
-                        window.inner(),
-                        notification.get(),
-                        &layouts as *const _ as *mut _,
-                    )
-                }
-                .into_result()
-                .whatever_context(format!(
-                    "Failed to observe window moves in {bundle_id}"
-                ))?;
-            }
+    Ok(())
+}
 
-            // SAFETY: todo
-            let run_loop_source =
-                unsafe { AXObserverGetRunLoopSource(observer.get()) };
-            if run_loop_source.is_null() {
-                return Err(UnnamedError::UnexpectedNull);
-            }
-            // SAFETY: todo
-            unsafe {
-                CFRunLoopAddSource(
-                    CFRunLoopGetCurrent(),
-                    run_loop_source,
-                    kCFRunLoopDefaultMode,
-                )
-            };
+#[cfg(not(target_os = "macos"))]
+fn run(args: RunArgs) -> Result<(), UnnamedError> {
+    // Still useful for checking a config file before copying it over.
+    Config::load(&args.config)?;
+    whatever!("unnamed can only manage windows on macOS");
+}
+
+#[cfg(target_os = "macos")]
+fn run(args: RunArgs) -> Result<(), UnnamedError> {
+    if !has_accessibility_permissions()? {
+        whatever!("This program needs accessibility permissions to work");
+    }
+
+    let config = Config::load(&args.config)?;
+    let screens =
+        get_screens(&config).whatever_context("Failed to compute layouts")?;
 
-            observers.push(observer);
+    let recorder = args
+        .record
+        .map(|path| {
+            File::create(&path)
+                .whatever_context(format!(
+                    "Failed to create trace file {}",
+                    path.display()
+                ))
+                .map(|file| Recorder::new(BufWriter::new(file)))
+        })
+        .transpose()?;
+
+    let mut daemon = Daemon {
+        backend: MacosBackend::new(on_backend_event)?,
+        state: State::new(
+            screens.clone(),
+            &config.core_apps,
+            config.rules.clone(),
+            config.keybindings.clone(),
+        ),
+        recorder,
+        config: config.clone(),
+        config_path: args.config,
+    };
+    daemon.record(&TraceEntry::Start {
+        screens,
+        core_apps: config.core_apps.clone(),
+        rules: config.rules.clone(),
+        keybindings: config.keybindings.into_iter().collect(),
+    });
+
+    for core_app in &config.core_apps {
+        let bundle_id = BundleID::try_from(core_app.bundle_id.as_str())
+            .whatever_context("Config contains an invalid bundle ID")?;
+        for app in daemon.backend.running_apps(bundle_id)? {
+            let windows = daemon.backend.window_infos(&app)?;
+            daemon.dispatch(Event::AppLaunched {
+                app: app.clone(),
+                windows,
+            });
+
+            daemon.backend.subscribe(&app).whatever_context(format!(
+                "Failed to observe windows in {bundle_id}"
+            ))?;
         }
     }
 
+    DAEMON.set(Some(daemon));
+
     let mut key_state = KeyState::default();
 
     // rdev automatically sets up the CGRunLoop
@@ -274,20 +425,17 @@ fn main() -> Result<(), UnnamedError> {
         EventType::KeyPress(key) => {
             key_state.press(key);
 
-            if let Some(new_layout) = if key_state.is_modified(Key::KeyH) {
-                Some(Some(Layout::Left))
-            } else if key_state.is_modified(Key::KeyL) {
-                Some(Some(Layout::Right))
-            } else if key_state.is_modified(Key::KeyC) {
-                Some(Some(Layout::Full))
-            } else if key_state.is_modified(Key::Space) {
-                Some(None)
-            } else {
-                //if key_state.is_modified(Key::Space) { todo figure out toggle
-                None
-            } {
-                update_layout_for_focused_window(new_layout, &layouts)
-                    .expect("Failed to update window layouts");
+            if let Some(key) = chord_key(key) {
+                let chord = Chord::new(key_state.modifiers(), key);
+                DAEMON.with_borrow_mut(|daemon| {
+                    if let Err(error) = daemon
+                        .as_mut()
+                        .expect("Initialized above")
+                        .dispatch_chord(chord)
+                    {
+                        eprintln!("error: {error}");
+                    }
+                });
             }
         }
         EventType::KeyRelease(key) => {
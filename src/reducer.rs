@@ -0,0 +1,1079 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The layout engine as a pure function from events to commands. Nothing in
+//! here talks to the window server: the platform layer turns what it observes
+//! into [`Event`]s and carries out the [`Command`]s that [`reduce`] returns.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{AppInfo, FRAME_TOLERANCE, WindowId, WindowInfo},
+    config::CoreApp,
+    geometry::Rect,
+    keybind::{Action, Chord},
+    layout::{
+        Layout, LayoutAlgorithm, Layouts, Slots, StackPosition,
+        WindowDescriptor, default_algorithms,
+    },
+    rules::{self, Rule},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub layout: Layout,
+    /// Whether windows are kept in `layout`. Windows that are not managed are
+    /// left wherever the user puts them.
+    pub managed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    KeyChord(Chord),
+    /// `app` became the frontmost app, `windows` are all of its windows, and
+    /// `focused_window` is the one of them with keyboard focus.
+    AppFocused {
+        app: AppInfo,
+        windows: Vec<WindowInfo>,
+        focused_window: Option<WindowId>,
+    },
+    /// `app` was found running, and `windows` are all of its windows.
+    AppLaunched {
+        app: AppInfo,
+        windows: Vec<WindowInfo>,
+    },
+    /// `window` is now at `frame`.
+    WindowMoved {
+        window: WindowId,
+        frame: Rect,
+    },
+    /// `window` was resized and is now at `frame`.
+    WindowResized {
+        window: WindowId,
+        frame: Rect,
+    },
+    WindowClosed {
+        window: WindowId,
+    },
+    /// The layouts of every screen, starting with the one holding the menu
+    /// bar.
+    ScreensChanged(Vec<Layouts>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    SetFrame {
+        window: WindowId,
+        frame: Rect,
+    },
+    /// Brings `window` in front of every other window.
+    Raise(WindowId),
+}
+
+/// A screen and the algorithms laying out the windows on it, which are
+/// separate from those of every other screen.
+struct Screen {
+    layouts: Layouts,
+    /// Keyed by the layout that picks them.
+    algorithms: BTreeMap<Layout, Box<dyn LayoutAlgorithm>>,
+}
+
+impl Screen {
+    fn new(layouts: Layouts) -> Self {
+        Self {
+            layouts,
+            algorithms: default_algorithms(),
+        }
+    }
+}
+
+struct TrackedWindow {
+    /// Of the app owning the window.
+    bundle_id: String,
+    /// The index of the screen the window is laid out on.
+    screen: usize,
+    /// Where the window was last seen, which is where it is unless it moved
+    /// without anyone saying so.
+    frame: Rect,
+    /// Starts out as the assignment of its app. Windows without one are left
+    /// wherever the user puts them.
+    assignment: Option<Assignment>,
+}
+
+/// How many times in a row a window is sent to the same frame while its app
+/// keeps putting it somewhere else before we stop trying.
+pub const MAX_REFUSALS: u32 = 3;
+
+/// A frame a window was sent to but hasn't been seen at yet.
+struct Attempt {
+    frame: Rect,
+    /// How many times in a row the window was sent to `frame`.
+    writes: u32,
+    /// Whether the window was seen somewhere else since it was last sent to
+    /// `frame`.
+    refused: bool,
+}
+
+pub struct State {
+    screens: Vec<Screen>,
+    keybindings: HashMap<Chord, Action>,
+    /// Keyed by bundle ID, what new windows of each app are assigned.
+    assignments: HashMap<String, Assignment>,
+    /// Override `assignments` for the new windows they match.
+    rules: Vec<Rule>,
+    windows: BTreeMap<WindowId, TrackedWindow>,
+    focused_app: Option<AppInfo>,
+    focused_window: Option<WindowId>,
+    /// Where every managed window was last laid out.
+    targets: BTreeMap<WindowId, Rect>,
+    attempts: BTreeMap<WindowId, Attempt>,
+}
+
+impl State {
+    /// Every app in `core_apps` starts out in its configured layout. `screens`
+    /// starts with the one holding the menu bar.
+    pub fn new(
+        screens: Vec<Layouts>,
+        core_apps: &[CoreApp],
+        rules: Vec<Rule>,
+        keybindings: HashMap<Chord, Action>,
+    ) -> Self {
+        Self {
+            screens: screens.into_iter().map(Screen::new).collect(),
+            keybindings,
+            assignments: core_apps
+                .iter()
+                .map(|app| {
+                    (
+                        app.bundle_id.clone(),
+                        Assignment {
+                            layout: app.layout.clone(),
+                            managed: app.managed,
+                        },
+                    )
+                })
+                .collect(),
+            rules,
+            windows: BTreeMap::new(),
+            focused_app: None,
+            focused_window: None,
+            targets: BTreeMap::new(),
+            attempts: BTreeMap::new(),
+        }
+    }
+
+    pub fn action(&self, chord: &Chord) -> Option<Action> {
+        self.keybindings.get(chord).cloned()
+    }
+
+    /// What new windows of the app with `bundle_id` are assigned.
+    pub fn assignment(&self, bundle_id: &str) -> Option<Assignment> {
+        self.assignments.get(bundle_id).cloned()
+    }
+
+    pub fn window_assignment(&self, window: WindowId) -> Option<Assignment> {
+        self.windows.get(&window)?.assignment.clone()
+    }
+
+    pub fn focused_app(&self) -> Option<&AppInfo> {
+        self.focused_app.as_ref()
+    }
+
+    pub fn focused_window(&self) -> Option<WindowId> {
+        self.focused_window
+    }
+
+    /// Makes windows in `layout` on the `screen`th screen be laid out by
+    /// `algorithm`, replacing whatever did so before, and lays everything out
+    /// again. Other screens are unaffected. Does nothing if there is no such
+    /// screen.
+    pub fn set_algorithm(
+        &mut self,
+        screen: usize,
+        layout: Layout,
+        algorithm: Box<dyn LayoutAlgorithm>,
+    ) -> Vec<Command> {
+        let Some(screen) = self.screens.get_mut(screen) else {
+            return vec![];
+        };
+        screen.algorithms.insert(layout, algorithm);
+        self.relayout([])
+    }
+
+    /// Where `window` was last seen, as read back from the backend when it
+    /// appeared, moved or resized.
+    pub fn window_frame(&self, window: WindowId) -> Option<Rect> {
+        self.windows.get(&window).map(|tracked| tracked.frame)
+    }
+
+    /// Where `window` is in a stack of windows sharing a frame, if it is in
+    /// one.
+    pub fn stack_position(&self, window: WindowId) -> Option<StackPosition> {
+        let screen = self.screens.get(self.window_screen(window)?)?;
+        screen
+            .algorithms
+            .values()
+            .find_map(|algorithm| algorithm.stack_position(window))
+    }
+
+    /// The index of the screen `window` is laid out on.
+    pub fn window_screen(&self, window: WindowId) -> Option<usize> {
+        self.windows.get(&window).map(|tracked| tracked.screen)
+    }
+
+    /// The index of the screen sharing the most area with `frame`, if any
+    /// does.
+    fn overlapping_screen(&self, frame: &Rect) -> Option<usize> {
+        self.screens
+            .iter()
+            .enumerate()
+            .filter_map(|(index, screen)| {
+                let overlap = screen.layouts.frame.intersection(frame)?;
+                Some((index, overlap.area()))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Like [`State::overlapping_screen`], but windows entirely off screen
+    /// belong to the one holding the menu bar.
+    fn screen_of(&self, frame: &Rect) -> usize {
+        self.overlapping_screen(frame).unwrap_or(0)
+    }
+
+    /// Where a window not seen before goes and what it is assigned, or
+    /// `None` if the rules say to ignore it. Without rules saying otherwise,
+    /// that is the screen it is currently on and whatever its app is
+    /// assigned, floating if it is a dialog, sheet or the like.
+    fn place_new_window(
+        &self,
+        bundle_id: &str,
+        window: &WindowInfo,
+    ) -> Option<(usize, Option<Assignment>)> {
+        let verdict = rules::evaluate(&self.rules, bundle_id, window);
+        if verdict.ignore == Some(true) {
+            return None;
+        }
+
+        let screen = verdict
+            .display
+            .map(|display| display.get() - 1)
+            .filter(|screen| *screen < self.screens.len())
+            .unwrap_or_else(|| self.screen_of(&window.frame));
+
+        let mut assignment = self.assignment(bundle_id);
+        if let Some(layout) = verdict.layout {
+            assignment = Some(Assignment {
+                layout,
+                managed: true,
+            });
+        }
+        let float = verdict.float.or((!window.is_standard()).then_some(true));
+        if let Some(float) = float
+            && let Some(assignment) = &mut assignment
+        {
+            assignment.managed = !float;
+        }
+
+        Some((screen, assignment))
+    }
+
+    /// Windows that were already known keep their screen and assignment,
+    /// and the rest are placed by [`State::place_new_window`].
+    fn track_windows(&mut self, app: &AppInfo, windows: Vec<WindowInfo>) {
+        let mut previous = BTreeMap::new();
+        self.windows.retain(|window, tracked| {
+            if tracked.bundle_id != app.bundle_id {
+                return true;
+            }
+            previous
+                .insert(*window, (tracked.screen, tracked.assignment.take()));
+            false
+        });
+
+        for window in windows {
+            let Some((screen, assignment)) = previous
+                .remove(&window.id)
+                .or_else(|| self.place_new_window(&app.bundle_id, &window))
+            else {
+                continue;
+            };
+            self.windows.insert(
+                window.id,
+                TrackedWindow {
+                    bundle_id: app.bundle_id.clone(),
+                    screen,
+                    frame: window.frame,
+                    assignment,
+                },
+            );
+        }
+    }
+
+    /// Switches to `layouts`, keeping windows and algorithms on whichever new
+    /// screen overlaps their old one the most. Windows on a screen that went
+    /// away move to the one holding the menu bar.
+    fn set_screens(&mut self, layouts: Vec<Layouts>) {
+        let old = std::mem::replace(
+            &mut self.screens,
+            layouts.into_iter().map(Screen::new).collect(),
+        );
+
+        let mut kept = BTreeSet::new();
+        let mut moves = Vec::with_capacity(old.len());
+        for screen in old {
+            let index = self.overlapping_screen(&screen.layouts.frame);
+            if let Some(index) = index
+                && kept.insert(index)
+            {
+                self.screens[index].algorithms = screen.algorithms;
+            }
+            moves.push(index.unwrap_or(0));
+        }
+
+        for tracked in self.windows.values_mut() {
+            tracked.screen = moves.get(tracked.screen).copied().unwrap_or(0);
+        }
+    }
+
+    /// Moves `window` `delta` screens further along, wrapping around at
+    /// either end.
+    fn move_window(&mut self, window: WindowId, delta: isize) {
+        let count = self.screens.len();
+        if count == 0 {
+            return;
+        }
+        if let Some(tracked) = self.windows.get_mut(&window) {
+            tracked.screen = (tracked.screen as isize + delta)
+                .rem_euclid(count as isize)
+                as usize;
+        }
+    }
+
+    /// Every managed window, grouped by its screen and then by its layout.
+    fn managed_windows(&self) -> Vec<BTreeMap<Layout, Vec<WindowDescriptor>>> {
+        let mut managed = self
+            .screens
+            .iter()
+            .map(|_| BTreeMap::<_, Vec<_>>::new())
+            .collect::<Vec<_>>();
+        for (window, tracked) in &self.windows {
+            let Some(assignment) = &tracked.assignment else {
+                continue;
+            };
+            if !assignment.managed {
+                continue;
+            }
+            let Some(screen) = managed.get_mut(tracked.screen) else {
+                continue;
+            };
+            screen.entry(assignment.layout.clone()).or_default().push(
+                WindowDescriptor {
+                    id: *window,
+                    bundle_id: tracked.bundle_id.clone(),
+                    layout: assignment.layout.clone(),
+                    focused: self.focused_window == Some(*window),
+                },
+            );
+        }
+        managed
+    }
+
+    /// Runs every algorithm of every screen on the windows assigned to it,
+    /// including those with no windows so they can forget old ones.
+    fn compute_targets(&mut self) -> BTreeMap<WindowId, Rect> {
+        let managed = self.managed_windows();
+        let Some(bounds) = self
+            .screens
+            .iter()
+            .map(|screen| screen.layouts.frame)
+            .reduce(|bounds, frame| bounds.union(&frame))
+        else {
+            return BTreeMap::new();
+        };
+
+        let mut targets = BTreeMap::new();
+        for (screen, mut managed) in self.screens.iter_mut().zip(managed) {
+            let frame = screen.layouts.frame;
+            let gaps = screen.layouts.gaps;
+
+            for (layout, algorithm) in &mut screen.algorithms {
+                algorithm.set_bounds(bounds);
+                let windows = managed.remove(layout).unwrap_or_default();
+                targets.extend(algorithm.layout(frame, &gaps, &windows));
+            }
+
+            let mut slots = Slots {
+                slots: &screen.layouts.slots,
+            };
+            let windows = managed.into_values().flatten().collect::<Vec<_>>();
+            targets.extend(slots.layout(frame, &gaps, &windows));
+        }
+
+        targets
+    }
+
+    /// Lays out every window again, moving `windows` and any others whose
+    /// frame changed since last time.
+    fn relayout(
+        &mut self,
+        windows: impl IntoIterator<Item = WindowId>,
+    ) -> Vec<Command> {
+        let windows = windows.into_iter().collect::<BTreeSet<_>>();
+        let targets = self.compute_targets();
+        let commands = targets
+            .iter()
+            .filter(|(window, frame)| {
+                windows.contains(window)
+                    || self.targets.get(window) != Some(frame)
+            })
+            .map(|(window, frame)| Command::SetFrame {
+                window: *window,
+                frame: *frame,
+            })
+            .collect::<Vec<_>>();
+
+        self.attempts
+            .retain(|window, _| targets.contains_key(window));
+        for command in &commands {
+            let Command::SetFrame { window, frame } = command else {
+                continue;
+            };
+            // The backend skips writing frames a window is already at, so
+            // nothing will be seen of it.
+            if self.windows.get(window).is_some_and(|tracked| {
+                tracked.frame.approx_eq(frame, FRAME_TOLERANCE)
+            }) {
+                self.attempts.remove(window);
+                continue;
+            }
+            let attempt = self.attempts.entry(*window).or_insert(Attempt {
+                frame: *frame,
+                writes: 0,
+                refused: false,
+            });
+            if attempt.frame != *frame {
+                attempt.frame = *frame;
+                attempt.writes = 0;
+            }
+            attempt.writes = attempt.writes.saturating_add(1);
+            attempt.refused = false;
+        }
+
+        self.targets = targets;
+        commands
+    }
+
+    /// Responds to `window` moving or resizing to `frame`, which is either
+    /// the echo of a move we made, the app refusing it, or the user.
+    fn window_changed(
+        &mut self,
+        window: WindowId,
+        frame: Rect,
+        moved: bool,
+    ) -> Vec<Command> {
+        if let Some(tracked) = self.windows.get_mut(&window) {
+            tracked.frame = frame;
+        }
+        let target = self.targets.get(&window).copied();
+        if target
+            .is_some_and(|target| target.approx_eq(&frame, FRAME_TOLERANCE))
+        {
+            self.attempts.remove(&window);
+            return vec![];
+        }
+
+        // Whoever moved it, it might be on another screen now.
+        if moved {
+            let screen = self.screen_of(&frame);
+            if let Some(tracked) = self.windows.get_mut(&window) {
+                tracked.screen = screen;
+            }
+        }
+
+        match self.attempts.get_mut(&window) {
+            // The app put the window somewhere else right after we moved it,
+            // such as by rounding its size to a whole number of text rows.
+            // Insisting would only make it jitter, so give up after a few
+            // tries until its target changes. A move and a resize can both be
+            // reported for one refusal, but only the first is answered.
+            Some(attempt) if Some(attempt.frame) == target => {
+                let retry = !attempt.refused && attempt.writes < MAX_REFUSALS;
+                attempt.refused = true;
+                if retry {
+                    self.relayout([window])
+                } else {
+                    self.relayout([])
+                }
+            }
+            _ => {
+                self.attempts.remove(&window);
+                self.relayout([window])
+            }
+        }
+    }
+
+    fn windows_of(&self, bundle_id: &str) -> Vec<WindowId> {
+        self.windows
+            .iter()
+            .filter(|(_, tracked)| tracked.bundle_id == bundle_id)
+            .map(|(window, _)| *window)
+            .collect()
+    }
+
+    /// Returns `None` if `window` is not known.
+    fn window_assignment_mut(
+        &mut self,
+        window: WindowId,
+    ) -> Option<&mut Assignment> {
+        Some(self.windows.get_mut(&window)?.assignment.get_or_insert(
+            Assignment {
+                layout: Layout::FULL,
+                managed: false,
+            },
+        ))
+    }
+}
+
+/// Carries out `action` if it does not move the focused window itself. Actions
+/// for the algorithms only affect the screen of the focused window, so they do
+/// nothing without one.
+fn reduce_global_action(
+    state: &mut State,
+    action: &Action,
+) -> Option<Vec<Command>> {
+    match action {
+        Action::SetLayout(_)
+        | Action::ToggleManaged
+        | Action::MoveToNextDisplay
+        | Action::MoveToPreviousDisplay => None,
+        Action::ReloadConfig => {
+            // Reading the config is I/O, so the daemon does it and then sends
+            // `Event::ScreensChanged`.
+            Some(vec![])
+        }
+        _ => {
+            let Some(screen) = state
+                .focused_window
+                .and_then(|window| state.window_screen(window))
+                .and_then(|screen| state.screens.get_mut(screen))
+            else {
+                return Some(vec![]);
+            };
+            let raised = screen
+                .algorithms
+                .values_mut()
+                .filter_map(|algorithm| algorithm.perform(action))
+                .collect::<Vec<_>>();
+            let mut commands = state.relayout([]);
+            commands.extend(raised.into_iter().map(Command::Raise));
+            Some(commands)
+        }
+    }
+}
+
+pub fn reduce(state: &mut State, event: Event) -> Vec<Command> {
+    match event {
+        Event::KeyChord(chord) => {
+            let Some(action) = state.action(&chord) else {
+                return vec![];
+            };
+            if let Some(commands) = reduce_global_action(state, &action) {
+                return commands;
+            }
+            let Some(window) = state.focused_window else {
+                return vec![];
+            };
+
+            match action {
+                Action::SetLayout(layout) => {
+                    if let Some(assignment) =
+                        state.window_assignment_mut(window)
+                    {
+                        *assignment = Assignment {
+                            layout,
+                            managed: true,
+                        };
+                    }
+                }
+                Action::ToggleManaged => {
+                    if let Some(assignment) =
+                        state.window_assignment_mut(window)
+                    {
+                        assignment.managed ^= true;
+                    }
+                }
+                Action::MoveToNextDisplay => state.move_window(window, 1),
+                Action::MoveToPreviousDisplay => state.move_window(window, -1),
+                _ => unreachable!("Handled above"),
+            }
+
+            state.attempts.remove(&window);
+            state.relayout([window])
+        }
+        Event::AppFocused {
+            app,
+            windows,
+            focused_window,
+        } => {
+            state.track_windows(&app, windows);
+            state.focused_app = Some(app);
+            state.focused_window = focused_window;
+            state.relayout([])
+        }
+        Event::AppLaunched { app, windows } => {
+            state.track_windows(&app, windows);
+            let windows = state.windows_of(&app.bundle_id);
+            state.relayout(windows)
+        }
+        Event::WindowMoved { window, frame } => {
+            state.window_changed(window, frame, true)
+        }
+        Event::WindowResized { window, frame } => {
+            state.window_changed(window, frame, false)
+        }
+        Event::WindowClosed { window } => {
+            state.windows.remove(&window);
+            if state.focused_window == Some(window) {
+                state.focused_window = None;
+            }
+            state.relayout([])
+        }
+        Event::ScreensChanged(layouts) => {
+            state.set_screens(layouts);
+            let windows = state.windows.keys().copied().collect::<Vec<_>>();
+            state.relayout(windows)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        keybind::{Key, Modifiers, default_keybindings},
+        layout::{Gaps, default_slots},
+    };
+
+    const PRIMARY: Rect = Rect::new(0.0, 0.0, 1000.0, 800.0);
+    const SECONDARY: Rect = Rect::new(1000.0, 0.0, 800.0, 600.0);
+    const KITTY: &str = "net.kovidgoyal.kitty";
+
+    fn layouts(frame: Rect) -> Layouts {
+        Layouts::new(frame, Gaps::default(), default_slots())
+    }
+
+    fn slot(frame: Rect, layout: &Layout) -> Rect {
+        layouts(frame).get(layout).unwrap()
+    }
+
+    fn state(screens: &[Rect], layout: Layout) -> State {
+        State::new(
+            screens.iter().copied().map(layouts).collect(),
+            &[CoreApp {
+                bundle_id: KITTY.into(),
+                layout,
+                managed: true,
+            }],
+            vec![],
+            default_keybindings(),
+        )
+    }
+
+    fn kitty() -> AppInfo {
+        AppInfo {
+            pid: 1,
+            bundle_id: KITTY.into(),
+        }
+    }
+
+    fn window(id: u64, frame: Rect) -> WindowInfo {
+        WindowInfo {
+            id: WindowId(id),
+            frame,
+            title: String::new(),
+            role: "AXWindow".into(),
+            subrole: Some("AXStandardWindow".into()),
+            modal: false,
+        }
+    }
+
+    /// A window that isn't a standard window, such as a dialog.
+    fn special(id: u64, role: &str, subrole: Option<&str>) -> WindowInfo {
+        WindowInfo {
+            role: role.into(),
+            subrole: subrole.map(Into::into),
+            ..window(id, Rect::new(100.0, 100.0, 400.0, 300.0))
+        }
+    }
+
+    fn launched(windows: Vec<WindowInfo>) -> Event {
+        Event::AppLaunched {
+            app: kitty(),
+            windows,
+        }
+    }
+
+    fn focused(windows: Vec<WindowInfo>) -> Event {
+        Event::AppFocused {
+            app: kitty(),
+            focused_window: windows.first().map(|window| window.id),
+            windows,
+        }
+    }
+
+    fn chord(modifiers: Modifiers, key: Key) -> Event {
+        Event::KeyChord(Chord::new(modifiers, key))
+    }
+
+    fn set_frame(window: u64, frame: Rect) -> Command {
+        Command::SetFrame {
+            window: WindowId(window),
+            frame,
+        }
+    }
+
+    /// Puts every window in the top-left 100x100 of the screen.
+    struct Corner;
+
+    impl LayoutAlgorithm for Corner {
+        fn layout(
+            &mut self,
+            frame: Rect,
+            _gaps: &Gaps,
+            windows: &[WindowDescriptor],
+        ) -> Vec<(WindowId, Rect)> {
+            let corner = Rect::new(frame.min_x(), frame.min_y(), 100.0, 100.0);
+            windows.iter().map(|window| (window.id, corner)).collect()
+        }
+    }
+
+    #[test]
+    fn custom_algorithms_only_affect_their_screen() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::LEFT);
+        reduce(
+            &mut state,
+            focused(vec![window(1, PRIMARY), window(2, SECONDARY)]),
+        );
+
+        let corner = Rect::new(1000.0, 0.0, 100.0, 100.0);
+        let commands = state.set_algorithm(1, Layout::LEFT, Box::new(Corner));
+        assert_eq!(commands, [set_frame(2, corner)]);
+
+        // New windows on that screen use it too.
+        assert_eq!(
+            reduce(
+                &mut state,
+                focused(vec![
+                    window(1, PRIMARY),
+                    window(2, SECONDARY),
+                    window(3, SECONDARY)
+                ])
+            ),
+            [set_frame(3, corner)]
+        );
+
+        // There is no third screen.
+        assert_eq!(state.set_algorithm(2, Layout::LEFT, Box::new(Corner)), []);
+    }
+
+    #[test]
+    fn launched_windows_go_in_their_slot() {
+        let mut state = state(&[PRIMARY], Layout::LEFT);
+        let commands = reduce(
+            &mut state,
+            Event::AppLaunched {
+                app: kitty(),
+                windows: vec![window(1, PRIMARY)],
+            },
+        );
+        assert_eq!(commands, [set_frame(1, slot(PRIMARY, &Layout::LEFT))]);
+
+        // Nothing changed, so nothing moves.
+        assert_eq!(reduce(&mut state, focused(vec![window(1, PRIMARY)])), []);
+    }
+
+    #[test]
+    fn only_standard_windows_are_laid_out_by_default() {
+        let mut state = state(&[PRIMARY], Layout::LEFT);
+        let commands = reduce(
+            &mut state,
+            launched(vec![
+                window(1, PRIMARY),
+                special(2, "AXWindow", Some("AXDialog")),
+                special(3, "AXSheet", None),
+                special(4, "AXWindow", Some("AXFloatingWindow")),
+                WindowInfo {
+                    modal: true,
+                    ..window(5, PRIMARY)
+                },
+            ]),
+        );
+        assert_eq!(commands, [set_frame(1, slot(PRIMARY, &Layout::LEFT))]);
+
+        let floating = Some(Assignment {
+            layout: Layout::LEFT,
+            managed: false,
+        });
+        for window in 2..=5 {
+            assert_eq!(state.window_assignment(WindowId(window)), floating);
+        }
+    }
+
+    #[test]
+    fn rules_can_lay_out_dialogs() {
+        let mut state = State::new(
+            vec![layouts(PRIMARY)],
+            &[CoreApp {
+                bundle_id: KITTY.into(),
+                layout: Layout::LEFT,
+                managed: true,
+            }],
+            vec![Rule {
+                subrole: Some("AXDialog".into()),
+                float: Some(false),
+                ..Rule::default()
+            }],
+            default_keybindings(),
+        );
+        let commands = reduce(
+            &mut state,
+            launched(vec![
+                special(1, "AXWindow", Some("AXDialog")),
+                special(2, "AXSheet", None),
+            ]),
+        );
+        assert_eq!(commands, [set_frame(1, slot(PRIMARY, &Layout::LEFT))]);
+    }
+
+    #[test]
+    fn windows_are_where_they_were_last_seen() {
+        let mut state = state(&[PRIMARY], Layout::LEFT);
+        let left = slot(PRIMARY, &Layout::LEFT);
+        reduce(&mut state, launched(vec![window(1, PRIMARY)]));
+        assert_eq!(state.window_frame(WindowId(1)), Some(PRIMARY));
+
+        reduce(
+            &mut state,
+            Event::WindowResized {
+                window: WindowId(1),
+                frame: left,
+            },
+        );
+        assert_eq!(state.window_frame(WindowId(1)), Some(left));
+
+        let dragged = Rect::new(50.0, 50.0, 300.0, 200.0);
+        reduce(
+            &mut state,
+            Event::WindowMoved {
+                window: WindowId(1),
+                frame: dragged,
+            },
+        );
+        assert_eq!(state.window_frame(WindowId(1)), Some(dragged));
+        assert_eq!(state.window_frame(WindowId(2)), None);
+    }
+
+    #[test]
+    fn keybinds_change_the_focused_window() {
+        let mut state = state(&[PRIMARY], Layout::LEFT);
+        reduce(&mut state, focused(vec![window(1, PRIMARY)]));
+
+        let right = chord(Modifiers::SUPER_SHIFT, Key::Char('l'));
+        assert_eq!(
+            reduce(&mut state, right.clone()),
+            [set_frame(1, slot(PRIMARY, &Layout::RIGHT))]
+        );
+        assert_eq!(
+            state.window_assignment(WindowId(1)),
+            Some(Assignment {
+                layout: Layout::RIGHT,
+                managed: true
+            })
+        );
+
+        // Pressing it again sends the window there again in case it strayed.
+        assert_eq!(
+            reduce(&mut state, right),
+            [set_frame(1, slot(PRIMARY, &Layout::RIGHT))]
+        );
+
+        let toggle = chord(Modifiers::SUPER_SHIFT, Key::Space);
+        assert_eq!(reduce(&mut state, toggle.clone()), []);
+        assert_eq!(
+            reduce(
+                &mut state,
+                Event::WindowMoved {
+                    window: WindowId(1),
+                    frame: PRIMARY
+                }
+            ),
+            []
+        );
+        assert_eq!(
+            reduce(&mut state, toggle),
+            [set_frame(1, slot(PRIMARY, &Layout::RIGHT))]
+        );
+    }
+
+    #[test]
+    fn keybinds_without_a_target_do_nothing() {
+        let mut state = state(&[PRIMARY], Layout::LEFT);
+        let right = chord(Modifiers::SUPER_SHIFT, Key::Char('l'));
+        assert_eq!(reduce(&mut state, right), []);
+
+        reduce(&mut state, focused(vec![window(1, PRIMARY)]));
+        let unbound = chord(Modifiers::SUPER, Key::Char('z'));
+        assert_eq!(reduce(&mut state, unbound), []);
+    }
+
+    #[test]
+    fn windows_move_between_screens() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::FULL);
+        reduce(&mut state, focused(vec![window(1, PRIMARY)]));
+        assert_eq!(state.window_screen(WindowId(1)), Some(0));
+
+        let next = chord(Modifiers::SUPER_SHIFT, Key::Right);
+        assert_eq!(
+            reduce(&mut state, next.clone()),
+            [set_frame(1, slot(SECONDARY, &Layout::FULL))]
+        );
+        assert_eq!(state.window_screen(WindowId(1)), Some(1));
+
+        // Moving past the last screen wraps around to the first.
+        assert_eq!(
+            reduce(&mut state, next),
+            [set_frame(1, slot(PRIMARY, &Layout::FULL))]
+        );
+    }
+
+    #[test]
+    fn unplugged_screens_give_back_their_windows() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::FULL);
+        reduce(
+            &mut state,
+            focused(vec![window(1, PRIMARY), window(2, SECONDARY)]),
+        );
+        assert_eq!(state.window_screen(WindowId(2)), Some(1));
+
+        let commands =
+            reduce(&mut state, Event::ScreensChanged(vec![layouts(PRIMARY)]));
+        let full = slot(PRIMARY, &Layout::FULL);
+        assert_eq!(commands, [set_frame(1, full), set_frame(2, full)]);
+        assert_eq!(state.window_screen(WindowId(2)), Some(0));
+    }
+
+    #[test]
+    fn dragging_a_window_to_another_screen_moves_it_there() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::LEFT);
+        reduce(&mut state, focused(vec![window(1, PRIMARY)]));
+
+        let dropped = Rect::new(1100.0, 100.0, 300.0, 200.0);
+        let commands = reduce(
+            &mut state,
+            Event::WindowMoved {
+                window: WindowId(1),
+                frame: dropped,
+            },
+        );
+        assert_eq!(commands, [set_frame(1, slot(SECONDARY, &Layout::LEFT))]);
+        assert_eq!(state.window_screen(WindowId(1)), Some(1));
+    }
+
+    #[test]
+    fn closed_windows_give_up_their_space() {
+        let mut state = state(&[PRIMARY], Layout::BSP);
+        let commands = reduce(
+            &mut state,
+            focused(vec![window(1, PRIMARY), window(2, PRIMARY)]),
+        );
+        assert_eq!(commands.len(), 2);
+
+        let commands = reduce(
+            &mut state,
+            Event::WindowClosed {
+                window: WindowId(1),
+            },
+        );
+        assert_eq!(commands, [set_frame(2, slot(PRIMARY, &Layout::FULL))]);
+        assert_eq!(state.focused_window(), None);
+        assert_eq!(state.window_screen(WindowId(1)), None);
+    }
+
+    #[test]
+    fn scrolled_away_windows_are_parked_off_every_screen() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::SCROLLING);
+        let commands = reduce(
+            &mut state,
+            focused(vec![
+                window(1, PRIMARY),
+                window(2, PRIMARY),
+                window(3, PRIMARY),
+            ]),
+        );
+        let Some(Command::SetFrame { frame, .. }) = commands.last() else {
+            panic!("expected the last column to be moved");
+        };
+        assert_eq!(frame.min_x(), SECONDARY.max_x());
+    }
+
+    #[test]
+    fn algorithm_keybinds_only_affect_the_focused_screen() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::MONOCLE);
+        reduce(
+            &mut state,
+            focused(vec![
+                window(1, SECONDARY),
+                window(2, SECONDARY),
+                window(3, PRIMARY),
+                window(4, PRIMARY),
+            ]),
+        );
+
+        let cycle = chord(Modifiers::SUPER, Key::Tab);
+        assert_eq!(
+            reduce(&mut state, cycle.clone()),
+            [Command::Raise(WindowId(2))]
+        );
+        assert_eq!(reduce(&mut state, cycle), [Command::Raise(WindowId(1))]);
+    }
+
+    #[test]
+    fn stack_positions_count_the_windows_of_one_screen() {
+        let mut state = state(&[PRIMARY, SECONDARY], Layout::MONOCLE);
+        reduce(
+            &mut state,
+            focused(vec![
+                window(1, PRIMARY),
+                window(2, PRIMARY),
+                window(3, SECONDARY),
+            ]),
+        );
+
+        let position = |state: &State, window| {
+            state
+                .stack_position(WindowId(window))
+                .map(|position| position.to_string())
+        };
+        assert_eq!(position(&state, 1).as_deref(), Some("1/2"));
+        assert_eq!(position(&state, 2).as_deref(), Some("2/2"));
+        assert_eq!(position(&state, 3).as_deref(), Some("1/1"));
+
+        reduce(&mut state, chord(Modifiers::SUPER_SHIFT, Key::Char('c')));
+        assert_eq!(position(&state, 1), None);
+        assert_eq!(position(&state, 2).as_deref(), Some("1/1"));
+    }
+}
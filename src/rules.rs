@@ -0,0 +1,383 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Rules decide what happens to a window when it is first seen, such as:
+//!
+//! ```toml
+//! [[rules]]
+//! bundle_id = "com.apple.*"
+//! title = "^(Settings|Preferences)"
+//! float = true
+//!
+//! [[rules]]
+//! bundle_id = "com.tinyspeck.slackmacgap"
+//! layout = "right"
+//! display = 2
+//! ```
+//!
+//! Every rule whose conditions all hold applies, in order, so later rules
+//! override what earlier ones said.
+
+use std::{fmt, num::NonZeroUsize};
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::{backend::WindowInfo, layout::Layout};
+
+/// A pattern where `*` matches any run of characters and `?` matches any one
+/// character, and which has to match all of the text.
+#[derive(Clone)]
+pub struct Glob {
+    source: String,
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(source: &str) -> Self {
+        let mut pattern = String::from("^");
+        for c in source.chars() {
+            match c {
+                '*' => pattern.push_str(".*"),
+                '?' => pattern.push('.'),
+                _ => {
+                    pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])))
+                }
+            }
+        }
+        pattern.push('$');
+        Self {
+            source: source.to_string(),
+            regex: Regex::new(&pattern)
+                .expect("Everything but wildcards is escaped"),
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+/// A regular expression that matches anywhere in the text unless anchored.
+#[derive(Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self, regex::Error> {
+        Regex::new(source).map(Self)
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl fmt::Debug for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Glob({:?})", self.source)
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pattern({:?})", self.0.as_str())
+    }
+}
+
+impl PartialEq for Glob {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Serialize for Glob {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Glob {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|source| Self::new(&source))
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        Self::new(&source).map_err(de::Error::custom)
+    }
+}
+
+/// What rules say to do with a window. Anything left as `None` is up to the
+/// app's assignment, except that windows that aren't
+/// [standard](WindowInfo::is_standard) float unless told not to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    /// Never lay out, move or track the window at all.
+    pub ignore: Option<bool>,
+    /// Leave the window wherever the user puts it.
+    pub float: Option<bool>,
+    pub layout: Option<Layout>,
+    /// 1-indexed, starting from the screen holding the menu bar.
+    pub display: Option<NonZeroUsize>,
+}
+
+/// Every condition that is set has to hold for the rule to apply.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rule {
+    pub bundle_id: Option<Glob>,
+    pub title: Option<Pattern>,
+    /// Such as `AXWindow`.
+    pub role: Option<String>,
+    /// Such as `AXStandardWindow` or `AXDialog`.
+    pub subrole: Option<String>,
+    pub modal: Option<bool>,
+    pub min_width: Option<f64>,
+    pub max_width: Option<f64>,
+    pub min_height: Option<f64>,
+    pub max_height: Option<f64>,
+
+    pub ignore: Option<bool>,
+    pub float: Option<bool>,
+    pub layout: Option<Layout>,
+    pub display: Option<NonZeroUsize>,
+}
+
+impl Rule {
+    /// Whether the rule applies to `window` of the app with `bundle_id`.
+    pub fn matches(&self, bundle_id: &str, window: &WindowInfo) -> bool {
+        let size = window.frame.size;
+        self.bundle_id
+            .as_ref()
+            .is_none_or(|glob| glob.is_match(bundle_id))
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&window.title))
+            && self.role.as_ref().is_none_or(|role| *role == window.role)
+            && self
+                .subrole
+                .as_ref()
+                .is_none_or(|subrole| window.subrole.as_ref() == Some(subrole))
+            && self.modal.is_none_or(|modal| modal == window.modal)
+            && self.min_width.is_none_or(|min| size.width >= min)
+            && self.max_width.is_none_or(|max| size.width <= max)
+            && self.min_height.is_none_or(|min| size.height >= min)
+            && self.max_height.is_none_or(|max| size.height <= max)
+    }
+}
+
+/// Applies every rule in `rules` that matches `window` of the app with
+/// `bundle_id`, in order.
+pub fn evaluate(
+    rules: &[Rule],
+    bundle_id: &str,
+    window: &WindowInfo,
+) -> Verdict {
+    let mut verdict = Verdict::default();
+    for rule in rules.iter().filter(|rule| rule.matches(bundle_id, window)) {
+        verdict.ignore = rule.ignore.or(verdict.ignore);
+        verdict.float = rule.float.or(verdict.float);
+        verdict.layout = rule.layout.clone().or(verdict.layout);
+        verdict.display = rule.display.or(verdict.display);
+    }
+    verdict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::WindowId, geometry::Rect};
+
+    const KITTY: &str = "net.kovidgoyal.kitty";
+
+    fn window(title: &str, width: f64, height: f64) -> WindowInfo {
+        WindowInfo {
+            id: WindowId(1),
+            frame: Rect::new(0.0, 0.0, width, height),
+            title: title.into(),
+            role: "AXWindow".into(),
+            subrole: Some("AXStandardWindow".into()),
+            modal: false,
+        }
+    }
+
+    fn dialog() -> WindowInfo {
+        WindowInfo {
+            subrole: Some("AXDialog".into()),
+            modal: true,
+            ..window("Open", 400.0, 300.0)
+        }
+    }
+
+    #[test]
+    fn globs_match_the_whole_bundle_id() {
+        let glob = Glob::new("com.apple.*");
+        assert!(glob.is_match("com.apple.Safari"));
+        assert!(!glob.is_match("org.com.apple.Safari"));
+        assert!(!glob.is_match("comXapple.Safari"));
+
+        let glob = Glob::new("net.?ovidgoyal.kitty");
+        assert!(glob.is_match(KITTY));
+        assert!(!glob.is_match("net.ovidgoyal.kitty"));
+    }
+
+    #[test]
+    fn titles_match_anywhere_unless_anchored() {
+        let rule = Rule {
+            title: Some(Pattern::new("Settings").unwrap()),
+            ..Rule::default()
+        };
+        assert!(rule.matches(KITTY, &window("kitty Settings", 800.0, 600.0)));
+        assert!(!rule.matches(KITTY, &window("kitty", 800.0, 600.0)));
+
+        let rule = Rule {
+            title: Some(Pattern::new("^(Settings|Preferences)").unwrap()),
+            ..Rule::default()
+        };
+        assert!(rule.matches(KITTY, &window("Preferences", 800.0, 600.0)));
+        assert!(!rule.matches(KITTY, &window("kitty Settings", 800.0, 600.0)));
+    }
+
+    #[test]
+    fn roles_and_subroles_match_exactly() {
+        let rule = Rule {
+            role: Some("AXWindow".into()),
+            subrole: Some("AXDialog".into()),
+            ..Rule::default()
+        };
+        assert!(rule.matches(KITTY, &dialog()));
+        assert!(!rule.matches(KITTY, &window("kitty", 800.0, 600.0)));
+
+        let rule = Rule {
+            role: Some("AXSheet".into()),
+            ..Rule::default()
+        };
+        assert!(!rule.matches(KITTY, &dialog()));
+
+        let rule = Rule {
+            modal: Some(true),
+            ..Rule::default()
+        };
+        assert!(rule.matches(KITTY, &dialog()));
+        assert!(!rule.matches(KITTY, &window("kitty", 800.0, 600.0)));
+    }
+
+    #[test]
+    fn size_bounds_are_inclusive() {
+        let rule = Rule {
+            min_width: Some(400.0),
+            max_width: Some(800.0),
+            min_height: Some(300.0),
+            max_height: Some(600.0),
+            ..Rule::default()
+        };
+        assert!(rule.matches(KITTY, &window("", 400.0, 600.0)));
+        assert!(rule.matches(KITTY, &window("", 800.0, 300.0)));
+        assert!(!rule.matches(KITTY, &window("", 399.0, 400.0)));
+        assert!(!rule.matches(KITTY, &window("", 801.0, 400.0)));
+        assert!(!rule.matches(KITTY, &window("", 600.0, 299.0)));
+        assert!(!rule.matches(KITTY, &window("", 600.0, 601.0)));
+    }
+
+    #[test]
+    fn every_condition_has_to_hold() {
+        let rule = Rule {
+            bundle_id: Some(Glob::new("com.apple.*")),
+            subrole: Some("AXDialog".into()),
+            ..Rule::default()
+        };
+        assert!(rule.matches("com.apple.Safari", &dialog()));
+        assert!(!rule.matches(KITTY, &dialog()));
+        assert!(!rule.matches("com.apple.Safari", &window("", 800.0, 600.0)));
+    }
+
+    #[test]
+    fn later_rules_override_earlier_ones() {
+        let rules = [
+            Rule {
+                float: Some(true),
+                layout: Some(Layout::LEFT),
+                display: NonZeroUsize::new(2),
+                ..Rule::default()
+            },
+            Rule {
+                subrole: Some("AXDialog".into()),
+                float: Some(false),
+                ..Rule::default()
+            },
+            Rule {
+                bundle_id: Some(Glob::new("com.apple.*")),
+                ignore: Some(true),
+                ..Rule::default()
+            },
+            Rule {
+                layout: Some(Layout::RIGHT),
+                ..Rule::default()
+            },
+        ];
+
+        assert_eq!(
+            evaluate(&rules, KITTY, &dialog()),
+            Verdict {
+                ignore: None,
+                float: Some(false),
+                layout: Some(Layout::RIGHT),
+                display: NonZeroUsize::new(2),
+            }
+        );
+        assert_eq!(
+            evaluate(&rules, KITTY, &window("kitty", 800.0, 600.0)),
+            Verdict {
+                ignore: None,
+                float: Some(true),
+                layout: Some(Layout::RIGHT),
+                display: NonZeroUsize::new(2),
+            }
+        );
+        assert_eq!(
+            evaluate(&rules, "com.apple.Safari", &dialog()).ignore,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn no_rules_say_nothing() {
+        assert_eq!(evaluate(&[], KITTY, &dialog()), Verdict::default());
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Traces of everything the daemon saw and did, one JSON object per line. A
+//! trace recorded on macOS can be replayed anywhere against a
+//! [`FakeBackend`], which makes layout bugs reproducible.
+
+use std::{fmt, io::Write};
+
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, whatever};
+
+use crate::{
+    UnnamedError,
+    backend::{WindowBackend, fake::FakeBackend},
+    config::CoreApp,
+    keybind::{Action, Chord},
+    layout::Layouts,
+    reducer::{Command, Event, State, reduce},
+    rules::Rule,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceEntry {
+    /// What the daemon started with. Always the first entry.
+    Start {
+        screens: Vec<Layouts>,
+        core_apps: Vec<CoreApp>,
+        rules: Vec<Rule>,
+        keybindings: Vec<(Chord, Action)>,
+    },
+    Event(Event),
+    /// A command that was carried out successfully in response to the most
+    /// recent event.
+    Applied(Command),
+}
+
+pub struct Recorder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes `entry` as a line and flushes it, so the trace is complete up
+    /// to the last event even if the daemon crashes.
+    pub fn record(&mut self, entry: &TraceEntry) -> Result<(), UnnamedError> {
+        serde_json::to_writer(&mut self.writer, entry)
+            .whatever_context("Failed to serialize trace entry")?;
+        self.writer
+            .write_all(b"\n")
+            .and_then(|_| self.writer.flush())
+            .whatever_context("Failed to write trace entry")
+    }
+}
+
+/// An event after which replaying applied different commands than were
+/// recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// The 1-indexed line of the event in the trace.
+    pub line: usize,
+    pub event: Event,
+    pub recorded: Vec<Command>,
+    pub replayed: Vec<Command>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "line {}: {:?}", self.line, self.event)?;
+        writeln!(f, "  recorded: {:?}", self.recorded)?;
+        write!(f, "  replayed: {:?}", self.replayed)
+    }
+}
+
+struct Step {
+    line: usize,
+    event: Event,
+    recorded: Vec<Command>,
+    replayed: Vec<Command>,
+}
+
+impl Step {
+    fn mismatch(self) -> Option<Mismatch> {
+        (self.recorded != self.replayed).then_some(Mismatch {
+            line: self.line,
+            event: self.event,
+            recorded: self.recorded,
+            replayed: self.replayed,
+        })
+    }
+}
+
+/// Makes sure the apps and windows named in `event` exist in `backend`, and
+/// that windows it closes do not.
+fn populate(backend: &mut FakeBackend, event: &Event) {
+    match event {
+        Event::AppFocused { app, windows, .. }
+        | Event::AppLaunched { app, windows } => {
+            backend.insert_app(app.clone());
+            for window in windows {
+                backend.insert_window(app, window.clone());
+            }
+        }
+        Event::WindowClosed { window } => backend.close_window(*window),
+        _ => {}
+    }
+}
+
+/// Feeds the events in `trace` through the layout engine against fake
+/// windows, returning every event whose commands differ from the recording.
+pub fn replay(trace: &str) -> Result<Vec<Mismatch>, UnnamedError> {
+    let mut lines = trace
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line, contents)| {
+            serde_json::from_str::<TraceEntry>(contents)
+                .whatever_context(format!("Failed to parse trace line {line}"))
+                .map(|entry| (line, entry))
+        });
+
+    let Some((
+        _,
+        TraceEntry::Start {
+            screens,
+            core_apps,
+            rules,
+            keybindings,
+        },
+    )) = lines.next().transpose()?
+    else {
+        whatever!("Trace does not begin with a start entry");
+    };
+
+    let mut state = State::new(
+        screens,
+        &core_apps,
+        rules,
+        keybindings.into_iter().collect(),
+    );
+    let mut backend = FakeBackend::new();
+    let mut step: Option<Step> = None;
+    let mut mismatches = vec![];
+
+    for entry in lines {
+        let (line, entry) = entry?;
+        match entry {
+            TraceEntry::Start { .. } => {
+                whatever!("Unexpected start entry on trace line {line}");
+            }
+            TraceEntry::Event(event) => {
+                mismatches.extend(step.take().and_then(Step::mismatch));
+
+                populate(&mut backend, &event);
+                let replayed = reduce(&mut state, event.clone());
+                for command in &replayed {
+                    backend.execute(command).whatever_context(format!(
+                        "Failed to replay trace line {line}"
+                    ))?;
+                }
+
+                step = Some(Step {
+                    line,
+                    event,
+                    recorded: vec![],
+                    replayed,
+                });
+            }
+            TraceEntry::Applied(command) => {
+                let Some(step) = step.as_mut() else {
+                    whatever!(
+                        "Trace line {line} applies a command before any event"
+                    );
+                };
+                step.recorded.push(command);
+            }
+        }
+    }
+    mismatches.extend(step.and_then(Step::mismatch));
+
+    Ok(mismatches)
+}
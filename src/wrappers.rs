@@ -12,39 +12,69 @@
 // You should have received a copy of the GNU General Public License along with
 // this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod attribute;
+
 use std::{borrow::Cow, ffi, ptr};
 
 use accessibility_sys::{
-    AXUIElementCopyAttributeValue, AXUIElementCreateApplication,
-    AXUIElementGetPid, AXUIElementRef, AXUIElementSetAttributeValue,
-    AXValueRef, kAXPositionAttribute, kAXSizeAttribute, kAXWindowsAttribute,
-    pid_t,
+    AXError, AXUIElementCopyAttributeValue, AXUIElementCreateApplication,
+    AXUIElementGetPid, AXUIElementPerformAction, AXUIElementRef,
+    AXUIElementSetAttributeValue, AXValueRef, kAXErrorAttributeUnsupported,
+    kAXErrorNoValue, kAXErrorSuccess, kAXFocusedAttribute,
+    kAXFocusedWindowAttribute, kAXMainAttribute, kAXMinimizedAttribute,
+    kAXModalAttribute, kAXParentAttribute, kAXPositionAttribute,
+    kAXRaiseAction, kAXRoleAttribute, kAXSizeAttribute, kAXSubroleAttribute,
+    kAXTitleAttribute, kAXWindowsAttribute, pid_t,
 };
 use cocoa::{
-    appkit::NSRunningApplication,
+    appkit::{NSApplicationActivateIgnoringOtherApps, NSRunningApplication},
     base::{id, nil},
-    foundation::{NSArray, NSString},
+    foundation::NSString,
 };
 use core_foundation_sys::{
-    base::{Boolean, kCFAllocatorNull},
+    base::{Boolean, CFHash, kCFAllocatorNull},
     string::{
         CFStringCreateWithBytesNoCopy, CFStringRef, kCFStringEncodingUTF8,
     },
 };
 use core_graphics::display::{CFIndex, CFTypeRef};
-use snafu::ResultExt;
+use snafu::{ResultExt, whatever};
 
 use crate::{
     AXErrorExt, BundleID, UnnamedError,
+    backend::WindowId,
+    geometry::Rect,
     layout::AXRect,
-    memory::{CopyOnWrite, ManageWithRc, Rc, Unique},
+    memory::{CopyOnWrite, Rc, Unique},
+    wrappers::attribute::{Attribute, Element, FromAXValue},
 };
 
+/// Not in the public headers, but supported by every window that can go full
+/// screen.
+const FULL_SCREEN_ATTRIBUTE: &str = "AXFullScreen";
+
+unsafe extern "C" {
+    /// Private, but the only way to get from an accessibility element to the
+    /// window server's ID for the window, which never changes while it is
+    /// open.
+    fn _AXUIElementGetWindow(element: AXUIElementRef, id: *mut u32) -> AXError;
+}
+
 #[derive(Clone, Copy)]
 pub enum AccessibilityElementKey {
     Position,
     Size,
     Windows,
+    FocusedWindow,
+    Title,
+    Role,
+    Subrole,
+    Modal,
+    Minimized,
+    FullScreen,
+    Main,
+    Focused,
+    Parent,
 }
 
 pub fn create_cfstring_from_static_str(
@@ -68,14 +98,27 @@ pub fn create_cfstring_from_static_str(
 }
 
 impl AccessibilityElementKey {
-    fn as_cfstring(&self) -> Result<Unique<CFStringRef>, UnnamedError> {
-        let string = match self {
+    /// Such as `AXPosition`.
+    pub fn name(&self) -> &'static str {
+        match self {
             AccessibilityElementKey::Position => kAXPositionAttribute,
             AccessibilityElementKey::Size => kAXSizeAttribute,
             AccessibilityElementKey::Windows => kAXWindowsAttribute,
-        };
+            AccessibilityElementKey::FocusedWindow => kAXFocusedWindowAttribute,
+            AccessibilityElementKey::Title => kAXTitleAttribute,
+            AccessibilityElementKey::Role => kAXRoleAttribute,
+            AccessibilityElementKey::Subrole => kAXSubroleAttribute,
+            AccessibilityElementKey::Modal => kAXModalAttribute,
+            AccessibilityElementKey::Minimized => kAXMinimizedAttribute,
+            AccessibilityElementKey::FullScreen => FULL_SCREEN_ATTRIBUTE,
+            AccessibilityElementKey::Main => kAXMainAttribute,
+            AccessibilityElementKey::Focused => kAXFocusedAttribute,
+            AccessibilityElementKey::Parent => kAXParentAttribute,
+        }
+    }
 
-        create_cfstring_from_static_str(string)
+    fn as_cfstring(&self) -> Result<Unique<CFStringRef>, UnnamedError> {
+        create_cfstring_from_static_str(self.name())
     }
 }
 
@@ -135,6 +178,34 @@ pub trait AccessibilityElement {
         // SAFETY: todo
         unsafe { Rc::new_const(result) }.ok_or(UnnamedError::UnexpectedNull)
     }
+
+    /// Returns `None` if the element has no value for `attribute`.
+    fn attribute<T: FromAXValue>(
+        &self,
+        attribute: Attribute<T>,
+    ) -> Result<Option<T>, UnnamedError> {
+        // SAFETY: The element is valid for as long as `self`.
+        let value = match unsafe { self.get(attribute.key()) } {
+            Ok(value) => value,
+            Err(UnnamedError::AXError { code })
+                if code == kAXErrorNoValue
+                    || code == kAXErrorAttributeUnsupported =>
+            {
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+
+        // SAFETY: `value` is a valid CoreFoundation object for as long as it
+        // is borrowed.
+        match unsafe { T::from_ax_value(value.get()) } {
+            Some(value) => Ok(Some(value)),
+            None => whatever!(
+                "Accessibility attribute {} has an unexpected type",
+                attribute.key().name()
+            ),
+        }
+    }
 }
 
 pub struct App<'a> {
@@ -164,10 +235,6 @@ impl<'a> App<'a> {
         // SAFETY: `app` is an `Rc`.
         let pid = unsafe { app.get().processIdentifier() };
 
-        // SAFETY: todo
-        let inner = unsafe { Rc::new_mut(AXUIElementCreateApplication(pid)) }
-            .ok_or(UnnamedError::CouldNotCreateCFObject)?;
-
         let bundle_id = if let Some(bundle_id) = bundle_id.into() {
             bundle_id.into()
         } else {
@@ -188,10 +255,21 @@ impl<'a> App<'a> {
             unsafe { ffi::CStr::from_ptr(bundle_id_cstr) }.to_string_lossy()
         };
 
+        Self::from_pid(pid, bundle_id)
+    }
+
+    pub fn from_pid(
+        pid: pid_t,
+        bundle_id: impl Into<Cow<'a, str>>,
+    ) -> Result<Self, UnnamedError> {
+        // SAFETY: todo
+        let inner = unsafe { Rc::new_mut(AXUIElementCreateApplication(pid)) }
+            .ok_or(UnnamedError::CouldNotCreateCFObject)?;
+
         Ok(Self {
             inner,
             pid,
-            bundle_id,
+            bundle_id: bundle_id.into(),
         })
     }
 
@@ -204,51 +282,43 @@ impl<'a> App<'a> {
     }
 
     pub fn get_windows(&self) -> Result<Box<[Window]>, UnnamedError> {
-        // SAFETY: todo
-        let windows = unsafe { self.get(AccessibilityElementKey::Windows) }
+        let windows = self
+            .attribute(attribute::WINDOWS)
             .whatever_context(
                 "Failed to get accessibility elements for app windows",
-            )?;
-
-        // SAFETY: `windows` is an `NSArray`.
-        let count = unsafe { NSArray::count(windows.get() as id) } as usize;
-
-        let mut ax_windows = Vec::with_capacity(count);
-        for i in 0..count {
-            // SAFETY: `runningApplicationsWithBundleIdentifier` returns an
-            // `NSArray`. Each element is managed by the `NSArray`, so we use
-            // `as_rc`.
-            let ax_window = unsafe {
-                (NSArray::objectAtIndex(windows.get() as id, i as u64)
-                    as AXUIElementRef)
-                    .as_rc()
-            }
+            )?
             .ok_or(UnnamedError::UnexpectedNull)?;
 
-            //let mut pid = 0;
-            //// SAFETY: todo
-            //unsafe { AXUIElementGetPid(ax_window.get(), &mut pid) }
-            //    .into_result()
-            //    .whatever_context(format!(
-            //        "Could not get {} window PID",
-            //        self.bundle_id
-            //    ))?;
+        Ok(windows
+            .into_iter()
+            .map(|element| self.window(element))
+            .collect())
+    }
 
-            // SAFETY: todo
-            ax_windows.push(Window {
-                inner: CopyOnWrite::Owned(ax_window),
-                //pid,
-                bundle_id: self.bundle_id.to_string(),
-            });
-        }
+    /// Returns `None` if the app has no windows or none of them are focused.
+    pub fn focused_window(&self) -> Result<Option<Window>, UnnamedError> {
+        Ok(self
+            .attribute(attribute::FOCUSED_WINDOW)
+            .whatever_context(format!(
+                "Failed to get focused window of {}",
+                self.bundle_id
+            ))?
+            .map(|element| self.window(element)))
+    }
 
-        Ok(ax_windows.into_boxed_slice())
+    /// `element` is one of the app's windows.
+    fn window(&self, element: Element) -> Window {
+        Window {
+            inner: CopyOnWrite::Owned(element.into_rc()),
+            pid: self.pid,
+            bundle_id: self.bundle_id.to_string(),
+        }
     }
 }
 
 pub struct Window {
     inner: CopyOnWrite<AXUIElementRef>,
-    //pid: pid_t,
+    pid: pid_t,
     bundle_id: String,
 }
 
@@ -309,7 +379,7 @@ impl Window {
 
         Ok(Self {
             inner: CopyOnWrite::Borrowed(element),
-            //pid,
+            pid,
             bundle_id,
         })
     }
@@ -334,7 +404,142 @@ impl Window {
         Ok(())
     }
 
+    /// Brings this window to the front of its app, and its app to the front
+    /// of every other app.
+    pub fn raise(&self) -> Result<(), UnnamedError> {
+        let action = create_cfstring_from_static_str(kAXRaiseAction)?;
+        // SAFETY: The element and the action are both valid for the duration
+        // of the call.
+        unsafe { AXUIElementPerformAction(self.inner(), action.get()) }
+            .into_result()
+            .whatever_context(format!("Failed to raise {}", self.bundle_id))?;
+
+        // SAFETY: `runningApplicationWithProcessIdentifier:` is a class method
+        // that accepts any PID.
+        let running_app = unsafe {
+            NSRunningApplication::runningApplicationWithProcessIdentifier(
+                nil, self.pid,
+            )
+        };
+        if running_app.is_null() {
+            return Err(UnnamedError::UnexpectedNull);
+        }
+        // SAFETY: `running_app` was just checked to not be null.
+        unsafe {
+            running_app
+                .activateWithOptions_(NSApplicationActivateIgnoringOtherApps)
+        };
+
+        Ok(())
+    }
+
+    /// Where the window currently is.
+    pub fn frame(&self) -> Result<Rect, UnnamedError> {
+        let origin = self
+            .attribute(attribute::POSITION)
+            .whatever_context(format!(
+                "Failed to get {} position",
+                self.bundle_id
+            ))?
+            .ok_or(UnnamedError::UnexpectedNull)?;
+        let size = self
+            .attribute(attribute::SIZE)
+            .whatever_context(format!("Failed to get {} size", self.bundle_id))?
+            .ok_or(UnnamedError::UnexpectedNull)?;
+
+        Ok(Rect { origin, size })
+    }
+
+    /// Empty for windows without a title.
+    pub fn title(&self) -> Result<String, UnnamedError> {
+        Ok(self
+            .attribute(attribute::TITLE)
+            .whatever_context(format!(
+                "Failed to get {} window title",
+                self.bundle_id
+            ))?
+            .unwrap_or_default())
+    }
+
+    /// Such as `AXWindow`.
+    pub fn role(&self) -> Result<String, UnnamedError> {
+        self.attribute(attribute::ROLE)
+            .whatever_context(format!(
+                "Failed to get {} window role",
+                self.bundle_id
+            ))?
+            .ok_or(UnnamedError::UnexpectedNull)
+    }
+
+    /// Such as `AXStandardWindow` or `AXDialog`.
+    pub fn subrole(&self) -> Result<Option<String>, UnnamedError> {
+        self.attribute(attribute::SUBROLE).whatever_context(format!(
+            "Failed to get {} window subrole",
+            self.bundle_id
+        ))
+    }
+
+    /// Whether the window blocks the rest of its app until it is dismissed.
+    pub fn is_modal(&self) -> Result<bool, UnnamedError> {
+        self.flag(attribute::MODAL)
+    }
+
+    /// Whether the window is in the Dock.
+    pub fn is_minimized(&self) -> Result<bool, UnnamedError> {
+        self.flag(attribute::MINIMIZED)
+    }
+
+    /// Whether the window is in its own full screen space.
+    pub fn is_full_screen(&self) -> Result<bool, UnnamedError> {
+        self.flag(attribute::FULL_SCREEN)
+    }
+
+    /// Whether the window is the one its app's menus act on.
+    pub fn is_main(&self) -> Result<bool, UnnamedError> {
+        self.flag(attribute::MAIN)
+    }
+
+    /// Whether the window gets its app's keyboard input.
+    pub fn is_focused(&self) -> Result<bool, UnnamedError> {
+        self.flag(attribute::FOCUSED)
+    }
+
+    /// Windows without a value for `attribute` count as `false`.
+    fn flag(&self, attribute: Attribute<bool>) -> Result<bool, UnnamedError> {
+        Ok(self
+            .attribute(attribute)
+            .whatever_context(format!(
+                "Failed to get {} of {} window",
+                attribute.key().name(),
+                self.bundle_id
+            ))?
+            .unwrap_or(false))
+    }
+
+    pub fn pid(&self) -> pid_t {
+        self.pid
+    }
+
     pub fn bundle_id(&self) -> BundleID {
         BundleID(&self.bundle_id)
     }
+
+    /// The window server's ID for the window, which stays the same for as
+    /// long as it is open no matter how the window is looked up. Falls back
+    /// to a hash of the accessibility element for windows without one, which
+    /// never collides with a window server ID.
+    pub fn id(&self) -> WindowId {
+        let mut id = 0;
+        // SAFETY: The element is valid for the duration of the call, and `id`
+        // is a valid `u32` to write into.
+        if unsafe { _AXUIElementGetWindow(self.inner(), &mut id) }
+            == kAXErrorSuccess
+        {
+            return WindowId(id.into());
+        }
+
+        // SAFETY: todo
+        let hash = unsafe { CFHash(self.inner() as CFTypeRef) };
+        WindowId(((self.pid as u32 as u64) << 32) | (hash as u32 as u64))
+    }
 }
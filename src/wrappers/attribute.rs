@@ -0,0 +1,258 @@
+// Copyright (C) 2024 Ethan Uppal.
+//
+// This program is free software: you can redistribute it and/or modify it under
+// the terms of the GNU General Public License as published by the Free Software
+// Foundation, version 3 of the License only.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License for more
+// details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Accessibility attributes paired with the type of their values, so that
+//! reading one never involves casting a `CFTypeRef` by hand:
+//!
+//! ```ignore
+//! let title = window.attribute(attribute::TITLE)?;
+//! ```
+
+use std::{ffi, marker::PhantomData};
+
+use accessibility_sys::{
+    AXUIElementGetTypeID, AXUIElementRef, AXValueGetType, AXValueGetTypeID,
+    AXValueGetValue, AXValueRef, AXValueType, kAXValueTypeCGPoint,
+    kAXValueTypeCGRect, kAXValueTypeCGSize,
+};
+use cocoa::{
+    base::id,
+    foundation::{NSArray, NSString},
+};
+use core_foundation_sys::{
+    array::CFArrayGetTypeID,
+    base::{CFGetTypeID, CFTypeRef},
+    number::{CFBooleanGetTypeID, CFBooleanGetValue, CFBooleanRef},
+    string::CFStringGetTypeID,
+};
+use core_graphics::display::{CGPoint, CGRect, CGSize};
+
+use super::{AccessibilityElement, AccessibilityElementKey};
+use crate::{
+    geometry::{Point, Rect, Size},
+    memory::{ManageWithRc, Rc},
+};
+
+/// An accessibility attribute whose values are `T`s.
+pub struct Attribute<T> {
+    key: AccessibilityElementKey,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Attribute<T> {
+    const fn new(key: AccessibilityElementKey) -> Self {
+        Self {
+            key,
+            value: PhantomData,
+        }
+    }
+
+    pub fn key(&self) -> AccessibilityElementKey {
+        self.key
+    }
+}
+
+impl<T> Clone for Attribute<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Attribute<T> {}
+
+pub const POSITION: Attribute<Point> =
+    Attribute::new(AccessibilityElementKey::Position);
+pub const SIZE: Attribute<Size> = Attribute::new(AccessibilityElementKey::Size);
+pub const WINDOWS: Attribute<Vec<Element>> =
+    Attribute::new(AccessibilityElementKey::Windows);
+pub const FOCUSED_WINDOW: Attribute<Element> =
+    Attribute::new(AccessibilityElementKey::FocusedWindow);
+pub const PARENT: Attribute<Element> =
+    Attribute::new(AccessibilityElementKey::Parent);
+pub const TITLE: Attribute<String> =
+    Attribute::new(AccessibilityElementKey::Title);
+pub const ROLE: Attribute<String> =
+    Attribute::new(AccessibilityElementKey::Role);
+pub const SUBROLE: Attribute<String> =
+    Attribute::new(AccessibilityElementKey::Subrole);
+pub const MODAL: Attribute<bool> =
+    Attribute::new(AccessibilityElementKey::Modal);
+pub const MINIMIZED: Attribute<bool> =
+    Attribute::new(AccessibilityElementKey::Minimized);
+pub const FULL_SCREEN: Attribute<bool> =
+    Attribute::new(AccessibilityElementKey::FullScreen);
+pub const MAIN: Attribute<bool> = Attribute::new(AccessibilityElementKey::Main);
+pub const FOCUSED: Attribute<bool> =
+    Attribute::new(AccessibilityElementKey::Focused);
+
+/// Any accessibility element, such as the parent of a window.
+#[derive(Clone)]
+pub struct Element(Rc<AXUIElementRef>);
+
+impl Element {
+    pub fn into_rc(self) -> Rc<AXUIElementRef> {
+        self.0
+    }
+}
+
+impl AccessibilityElement for Element {
+    unsafe fn inner(&self) -> AXUIElementRef {
+        // SAFETY: The returned element is only used while `self` is borrowed.
+        unsafe { self.0.get() }
+    }
+}
+
+/// A type that values of accessibility attributes can be decoded into.
+pub trait FromAXValue: Sized {
+    /// Returns `None` if `value` is of some other type.
+    ///
+    /// # Safety
+    ///
+    /// `value` is a valid CoreFoundation object.
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self>;
+}
+
+impl FromAXValue for String {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        // SAFETY: `value` is a valid CoreFoundation object.
+        if unsafe { CFGetTypeID(value) != CFStringGetTypeID() } {
+            return None;
+        }
+
+        // SAFETY: `value` is a `CFString`, which is toll-free bridged with
+        // `NSString`, and the buffer lives as long as `value`.
+        let cstr = unsafe { NSString::UTF8String(value as id) };
+        if cstr.is_null() {
+            return None;
+        }
+
+        // SAFETY: `cstr` was just checked to not be null and is
+        // null-terminated.
+        Some(
+            unsafe { ffi::CStr::from_ptr(cstr) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+impl FromAXValue for bool {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        // SAFETY: `value` is a valid CoreFoundation object.
+        if unsafe { CFGetTypeID(value) != CFBooleanGetTypeID() } {
+            return None;
+        }
+
+        // SAFETY: `value` was just checked to be a `CFBoolean`.
+        Some(unsafe { CFBooleanGetValue(value as CFBooleanRef) })
+    }
+}
+
+/// Copies the `kind` of struct wrapped by `value` into `decoded`.
+///
+/// # Safety
+///
+/// `value` is a valid CoreFoundation object, and `T` is the struct that
+/// `kind` stands for.
+unsafe fn decode_ax_value<T>(
+    value: CFTypeRef,
+    kind: AXValueType,
+    mut decoded: T,
+) -> Option<T> {
+    // SAFETY: `value` is a valid CoreFoundation object.
+    if unsafe { CFGetTypeID(value) != AXValueGetTypeID() } {
+        return None;
+    }
+    // SAFETY: `value` was just checked to be an `AXValueRef`.
+    if unsafe { AXValueGetType(value as AXValueRef) } != kind {
+        return None;
+    }
+
+    // SAFETY: `value` wraps a `kind`, which `decoded` is big enough to hold.
+    unsafe {
+        AXValueGetValue(
+            value as AXValueRef,
+            kind,
+            &mut decoded as *mut T as *mut _,
+        )
+    }
+    .then_some(decoded)
+}
+
+impl FromAXValue for Point {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        // SAFETY: `kAXValueTypeCGPoint` stands for `CGPoint`.
+        unsafe {
+            decode_ax_value(value, kAXValueTypeCGPoint, CGPoint::new(0.0, 0.0))
+        }
+        .map(Into::into)
+    }
+}
+
+impl FromAXValue for Size {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        // SAFETY: `kAXValueTypeCGSize` stands for `CGSize`.
+        unsafe {
+            decode_ax_value(value, kAXValueTypeCGSize, CGSize::new(0.0, 0.0))
+        }
+        .map(Into::into)
+    }
+}
+
+impl FromAXValue for Rect {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        let empty =
+            CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(0.0, 0.0));
+        // SAFETY: `kAXValueTypeCGRect` stands for `CGRect`.
+        unsafe { decode_ax_value(value, kAXValueTypeCGRect, empty) }
+            .map(Into::into)
+    }
+}
+
+impl FromAXValue for Element {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        // SAFETY: `value` is a valid CoreFoundation object.
+        if unsafe { CFGetTypeID(value) != AXUIElementGetTypeID() } {
+            return None;
+        }
+
+        // SAFETY: `value` was just checked to be an `AXUIElementRef`, which
+        // `as_rc` retains so it outlives `value`.
+        unsafe { (value as AXUIElementRef).as_rc() }.map(Self)
+    }
+}
+
+impl<T: FromAXValue> FromAXValue for Vec<T> {
+    unsafe fn from_ax_value(value: CFTypeRef) -> Option<Self> {
+        // SAFETY: `value` is a valid CoreFoundation object.
+        if unsafe { CFGetTypeID(value) != CFArrayGetTypeID() } {
+            return None;
+        }
+
+        // SAFETY: `value` was just checked to be a `CFArray`, which is
+        // toll-free bridged with `NSArray`.
+        let count = unsafe { NSArray::count(value as id) };
+        (0..count)
+            .map(|index| {
+                // SAFETY: `index` is in bounds, and every element of a
+                // `CFArray` is a valid CoreFoundation object.
+                unsafe {
+                    T::from_ax_value(
+                        NSArray::objectAtIndex(value as id, index) as CFTypeRef
+                    )
+                }
+            })
+            .collect()
+    }
+}